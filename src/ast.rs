@@ -1,18 +1,165 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct CssSize(String);
+/// The `font-size` of `[[size]]`. It is written back from its parts, so nothing else can reach the style attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub enum CssSize {
+  Length(CssLength),
+  Keyword(CssSizeKeyword),
+}
+
 impl CssSize {
-  pub fn new(value: &str) -> Self {
-    todo!()
+  /// Reads a number with a unit (`150%`, `1.5em`, `12px`) or a keyword (`x-large`, `smaller`), ignoring case.
+  /// Returns `None` for anything else, including negative numbers, exponents and numbers without a unit.
+  pub fn new(value: &str) -> Option<Self> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(keyword) = CssSizeKeyword::from(&value) {
+      return Some(Self::Keyword(keyword));
+    }
+
+    let (number, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len()));
+    // f64のparseは"1e3"や"inf"も読むので, 数字と小数点1つだけであることを先に確かめる
+    if !number.contains(|c: char| c.is_ascii_digit()) || number.matches('.').count() > 1 {
+      return None;
+    }
+    Some(Self::Length(CssLength::new(number.parse().ok()?, CssUnit::from(unit)?)?))
+  }
+
+  pub fn into_string(self) -> String {
+    self.to_string()
+  }
+}
+
+impl std::fmt::Display for CssSize {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Length(length) => write!(f, "{}{}", length.value, length.unit.name()),
+      Self::Keyword(keyword) => write!(f, "{}", keyword.name()),
+    }
+  }
+}
+
+/// A finite, non-negative number with a unit. The fields stay private so that `Eq` holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CssLength {
+  value: f64,
+  unit: CssUnit,
+}
+
+impl Eq for CssLength {} // newはNaNを通さない
+
+impl CssLength {
+  /// Returns `None` when `value` is negative, infinite or NaN.
+  pub fn new(value: f64, unit: CssUnit) -> Option<Self> {
+    (value.is_finite() && value >= 0.0).then_some(Self { value, unit })
   }
-  pub fn as_string(self) -> String {
-    self.0
+
+  pub fn value(self) -> f64 {
+    self.value
+  }
+
+  pub fn unit(self) -> CssUnit {
+    self.unit
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CssUnit {
+  Percent,
+  Em,
+  Rem,
+  Px,
+  Pt,
+}
+
+impl CssUnit {
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Percent => "%",
+      Self::Em => "em",
+      Self::Rem => "rem",
+      Self::Px => "px",
+      Self::Pt => "pt",
+    }
+  }
+
+  pub fn from(value: &str) -> Option<Self> {
+    match value {
+      "%" => Some(Self::Percent),
+      "em" => Some(Self::Em),
+      "rem" => Some(Self::Rem),
+      "px" => Some(Self::Px),
+      "pt" => Some(Self::Pt),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CssSizeKeyword {
+  XxSmall,
+  XSmall,
+  Small,
+  Medium,
+  Large,
+  XLarge,
+  XxLarge,
+  Smaller,
+  Larger,
+}
+
+impl CssSizeKeyword {
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::XxSmall => "xx-small",
+      Self::XSmall => "x-small",
+      Self::Small => "small",
+      Self::Medium => "medium",
+      Self::Large => "large",
+      Self::XLarge => "x-large",
+      Self::XxLarge => "xx-large",
+      Self::Smaller => "smaller",
+      Self::Larger => "larger",
+    }
+  }
+
+  pub fn from(value: &str) -> Option<Self> {
+    match value {
+      "xx-small" => Some(Self::XxSmall),
+      "x-small" => Some(Self::XSmall),
+      "small" => Some(Self::Small),
+      "medium" => Some(Self::Medium),
+      "large" => Some(Self::Large),
+      "x-large" => Some(Self::XLarge),
+      "xx-large" => Some(Self::XxLarge),
+      "smaller" => Some(Self::Smaller),
+      "larger" => Some(Self::Larger),
+      _ => None,
+    }
+  }
+}
+
+impl TryFrom<String> for CssSize {
+  type Error = String;
+
+  fn try_from(value: String) -> Result<Self, Self::Error> {
+    Self::new(&value).ok_or_else(|| format!("`{}` is not a valid CSS size", value))
+  }
+}
+
+impl From<CssSize> for String {
+  fn from(value: CssSize) -> Self {
+    value.into_string()
   }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Url(pub String); // TODO validate
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum WikidotColor {
   Aqua,
   Black,
@@ -33,6 +180,33 @@ pub enum WikidotColor {
 }
 
 impl WikidotColor {
+  pub const ALL: [Self; 16] = [
+    Self::Aqua, Self::Black, Self::Blue, Self::Fuchsia, Self::Grey, Self::Green, Self::Lime, Self::Maroon,
+    Self::Navy, Self::Olive, Self::Purple, Self::Red, Self::Silver, Self::Teal, Self::White, Self::Yellow,
+  ];
+
+  /// The name written in `##name|...##`.
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Aqua => "aqua",
+      Self::Black => "black",
+      Self::Blue => "blue",
+      Self::Fuchsia => "fuchsia",
+      Self::Grey => "grey",
+      Self::Green => "green",
+      Self::Lime => "lime",
+      Self::Maroon => "maroon",
+      Self::Navy => "navy",
+      Self::Olive => "olive",
+      Self::Purple => "purple",
+      Self::Red => "red",
+      Self::Silver => "silver",
+      Self::Teal => "teal",
+      Self::White => "white",
+      Self::Yellow => "yellow",
+    }
+  }
+
   pub fn from(value: &str) -> Option<Self> {
     match value.trim().to_lowercase().as_str() {
       "aqua" => { Some(Self::Aqua) },
@@ -54,10 +228,136 @@ impl WikidotColor {
       _ => { None }
     }
   }
+
+  pub fn rgb(self) -> (u8, u8, u8) {
+    match self {
+      Self::Aqua    => (0x00, 0xFF, 0xFF),
+      Self::Black   => (0x00, 0x00, 0x00),
+      Self::Blue    => (0x00, 0x00, 0xFF),
+      Self::Fuchsia => (0xFF, 0x00, 0xFF), // magenta
+      Self::Grey    => (0x80, 0x80, 0x80),
+      Self::Green   => (0x00, 0x80, 0x00),
+      Self::Lime    => (0x00, 0xFF, 0x00),
+      Self::Maroon  => (0x80, 0x00, 0x00),
+      Self::Navy    => (0x00, 0x00, 0x80),
+      Self::Olive   => (0x80, 0x80, 0x00),
+      Self::Purple  => (0x80, 0x00, 0x80),
+      Self::Red     => (0xFF, 0x00, 0x00),
+      Self::Silver  => (0xC0, 0xC0, 0xC0),
+      Self::Teal    => (0x00, 0x80, 0x80),
+      Self::White   => (0xFF, 0xFF, 0xFF),
+      Self::Yellow  => (0xFF, 0xFF, 0x00),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ListKind {
+  Unordered, // *
+  Ordered, // #
+}
+
+/// Where the hide label of a collapsible block is shown while it is unfolded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum HideLocation {
+  Top,
+  Bottom,
+  Both,
+  Neither,
+}
+
+impl HideLocation {
+  /// The value written in `hideLocation="..."`.
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Top => "top",
+      Self::Bottom => "bottom",
+      Self::Both => "both",
+      Self::Neither => "neither",
+    }
+  }
+
+  pub fn from(value: &str) -> Option<Self> {
+    match value.trim().to_lowercase().as_str() {
+      "top" => { Some(Self::Top) },
+      "bottom" => { Some(Self::Bottom) },
+      "both" => { Some(Self::Both) },
+      "neither" => { Some(Self::Neither) },
+      _ => { None }
+    }
+  }
+}
+
+/// The side `[[f<toc]]` and `[[f>toc]]` float to, or the side `~~~~<` and `~~~~>` clear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Float {
+  Left, // f<
+  Right, // f>
+}
+
+/// How `[[<image]]`, `[[=image]]` and `[[>image]]` place an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Alignment {
+  Left, // <
+  Right, // >
+  Center, // =
+}
+
+/// The `text-align` of `[[<]]`, `[[>]]`, `[[=]]` and `[[==]]` blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum TextAlign {
+  Left, // <
+  Right, // >
+  Center, // =
+  Justify, // ==
+}
+
+impl TextAlign {
+  /// The name of the element: `<`, `>`, `=` or `==`.
+  pub fn from_element_name(name: &str) -> Option<Self> {
+    match name {
+      "<" => Some(TextAlign::Left),
+      ">" => Some(TextAlign::Right),
+      "=" => Some(TextAlign::Center),
+      "==" => Some(TextAlign::Justify),
+      _ => None,
+    }
+  }
+
+  pub fn element_name(self) -> &'static str {
+    match self {
+      TextAlign::Left => "<",
+      TextAlign::Right => ">",
+      TextAlign::Center => "=",
+      TextAlign::Justify => "==",
+    }
+  }
+
+  /// The value of the CSS `text-align` property.
+  pub fn css_value(self) -> &'static str {
+    match self {
+      TextAlign::Left => "left",
+      TextAlign::Right => "right",
+      TextAlign::Center => "center",
+      TextAlign::Justify => "justify",
+    }
+  }
 }
 
 pub mod table_cell {
   #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+  #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
   pub enum Style {
     LeftAligned,
     RightAligned,
@@ -66,13 +366,14 @@ pub mod table_cell {
   }
 
   #[derive(Debug, Clone, PartialEq, Eq)]
-  pub struct BlockCell {
+  pub(crate) struct BlockCell {
     pub val: Vec<crate::tokenizer::Token>,
     pub style: Option<Style>,
     pub spanning: std::num::NonZeroUsize,
   }
 
   #[derive(Debug, Clone, PartialEq, Eq)]
+  #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
   pub struct Cell {
     pub val: Vec<crate::ast::TreeElement>,
     pub style: Option<Style>,
@@ -80,7 +381,18 @@ pub mod table_cell {
   }
 }
 
+/// A node of the parsed page.
+///
+/// With the `serde` feature the tree serializes as adjacently tagged JSON: every node is an object whose
+/// `"type"` is the variant name in snake_case and whose `"value"` holds the variant's data, so
+/// `[{"type": "paragraph", "value": [{"type": "bold", "value": [{"type": "text", "value": "a"}]}]}, {"type": "new_line"}]`
+/// is a paragraph holding bold text followed by a line break. Variants with named fields put them in an object
+/// (`{"type": "footnote", "value": {"id": 1, "children": [...]}}`), and `new_line` has no `"value"`.
+/// `CssSize` and `Url` are plain strings, enums such as `ListKind` and `HideLocation` are lowercase strings,
+/// and a table is an array of rows of `{"val": [...], "style": "title" | "left_aligned" | ... | null, "spanning": 1}`.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value", rename_all = "snake_case"))]
 pub enum TreeElement {
   Paragraph(Vec<TreeElement>),
   Text(String),
@@ -95,19 +407,85 @@ pub enum TreeElement {
   Size{scale: CssSize, children: Vec<TreeElement>}, // scaleは有効なCSS値
   Link{href: Url, open_in_new_tab: bool, name: String}, // TODO implement parsing name as wikidot string
   InternalLink{href: String, open_in_new_tab: bool, name: String}, // TODO implement parsing name as wikidot string
-  Collapsible(Vec<TreeElement>), // TODO fix: show open/close message
+  Collapsible{show: String, hide: String, folded: bool, hide_location: HideLocation, children: Vec<TreeElement>}, // foldedは初期状態で閉じているか
   Footnote{id: u32, children: Vec<TreeElement>}, // idは構文解析時に自動的に生成
+  FootnoteBlock{title: Option<String>}, // [[footnoteblock]]がなければページ末尾に自動的に追加される
   QuoteBlock(Vec<TreeElement>),
   Iframe(String), // the value is raw HTML element string
+  CodeBlock{language: Option<String>, source: String}, // sourceはエスケープ前の生の文字列
   Tab{
     title: String,
     children: Vec<TreeElement>,
   },
   TabView(Vec<TreeElement>), // only holds Tabs
   Table(Vec<Vec<table_cell::Cell>>),
+  Heading{level: u8, children: Vec<TreeElement>, anchor: Option<String>}, // anchorは目次に載る見出しのみ(toc0, toc1, ...)
+  List{kind: ListKind, children: Vec<TreeElement>}, // only holds ListItems
+  ListItem(Vec<TreeElement>),
   NewLine,
 
   HtmlElement{tag: String, property: Vec<(String, String)>, children: Vec<TreeElement>},
+  TableOfContents{float: Option<Float>}, // [[toc]]. 見出しは描画時にページ全体から集める
+  HorizontalRule, // ----
+  ClearFloat(Option<Float>), // ~~~~, ~~~~<, ~~~~>, [[clearfloat]]. Noneは両側
+  Align{alignment: TextAlign, children: Vec<TreeElement>}, // [[=]] ~ [[/=]]
+  DefinitionList(Vec<(Vec<TreeElement>, Vec<TreeElement>)>), // (用語, 説明)の並び
+  Image{
+    source: String, // 書かれたままのファイル名かURL
+    alt: Option<String>,
+    title: Option<String>,
+    width: Option<u32>, // px
+    height: Option<u32>,
+    link: Option<String>, // URLかページ名
+    alignment: Option<Alignment>,
+    float: bool, // [[f<image]], [[f>image]]
+    class: Option<String>,
+    style: Option<String>,
+  },
+}
+
+impl TreeElement {
+  /// Direct children in document order. Table cells are visited row by row,
+  /// and each item of a definition list gives its term and then its definition.
+  pub fn children(&self) -> Vec<&TreeElement> {
+    match self {
+      TreeElement::Paragraph(children)
+      | TreeElement::Bold(children)
+      | TreeElement::Italics(children)
+      | TreeElement::Underline(children)
+      | TreeElement::Strikethrough(children)
+      | TreeElement::Monospaced(children)
+      | TreeElement::Superscript(children)
+      | TreeElement::Subscript(children)
+      | TreeElement::Colored { children, .. }
+      | TreeElement::Size { children, .. }
+      | TreeElement::Collapsible { children, .. }
+      | TreeElement::Footnote { children, .. }
+      | TreeElement::QuoteBlock(children)
+      | TreeElement::Tab { children, .. }
+      | TreeElement::TabView(children)
+      | TreeElement::Heading { children, .. }
+      | TreeElement::List { children, .. }
+      | TreeElement::ListItem(children)
+      | TreeElement::HtmlElement { children, .. }
+      | TreeElement::Align { children, .. } => children.iter().collect(),
+
+      TreeElement::Table(rows) => rows.iter().flatten().flat_map(|cell| cell.val.iter()).collect(),
+      TreeElement::DefinitionList(items) => items.iter().flat_map(|(term, definition)| term.iter().chain(definition)).collect(),
+
+      TreeElement::Text(_)
+      | TreeElement::Link { .. }
+      | TreeElement::InternalLink { .. }
+      | TreeElement::FootnoteBlock { .. }
+      | TreeElement::Iframe(_)
+      | TreeElement::CodeBlock { .. }
+      | TreeElement::NewLine
+      | TreeElement::TableOfContents { .. }
+      | TreeElement::HorizontalRule
+      | TreeElement::ClearFloat(_)
+      | TreeElement::Image { .. } => vec![],
+    }
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -121,23 +499,28 @@ pub enum ParseFrame {
   Superscript,
   Subscript,
   Colored{red: u8, green: u8, blue: u8},
-  Size{scale: CssSize},
+  Size{scale: Option<CssSize>}, // 値が不正ならNone. 閉じタグとは対応するが要素にはならない
   // Link does not contain children
   // InternalLink does not contain children
-  Collapsible,
-  Footnote{id: u32}, // TODO implement [[footnote]] syntax
+  Collapsible{show: String, hide: String, folded: bool, hide_location: HideLocation},
+  Footnote{id: u32},
   QuoteBlock,
   // Iframe is a single element. The values are written in HTML and they won't be parsed.
   Tab(String),
   TabView, // this is a div element internally, just for showing renderers begin of TabView
   // Table does not contain TreeElement children
+  List{kind: ListKind},
+  ListItem,
 
   HtmlElement{tag: String, property: Vec<(String, String)>}, // should be filtered by its tag
+  Align(TextAlign),
 }
 
 impl ParseFrame {
-  pub fn into_tree_element(self, children: Vec<TreeElement>) -> TreeElement {
-    match self {
+  /// The element made from `children`, or `Err(children)` for a frame that makes none
+  /// (`[[size]]` with an invalid value), whose children then belong to the enclosing frame.
+  pub fn into_tree_element(self, children: Vec<TreeElement>) -> Result<TreeElement, Vec<TreeElement>> {
+    Ok(match self {
       ParseFrame::Paragraph => TreeElement::Paragraph(children),
       ParseFrame::Bold => TreeElement::Bold(children),
       ParseFrame::Italics => TreeElement::Italics(children),
@@ -147,14 +530,18 @@ impl ParseFrame {
       ParseFrame::Superscript => TreeElement::Superscript(children),
       ParseFrame::Subscript => TreeElement::Subscript(children),
       ParseFrame::Colored{red, green, blue} => TreeElement::Colored{red, green, blue, children},
-      ParseFrame::Size{scale} => TreeElement::Size{scale, children},
-      ParseFrame::Collapsible => TreeElement::Collapsible(children),
+      ParseFrame::Size{scale: Some(scale)} => TreeElement::Size{scale, children},
+      ParseFrame::Size{scale: None} => return Err(children),
+      ParseFrame::Collapsible{show, hide, folded, hide_location} => TreeElement::Collapsible{show, hide, folded, hide_location, children},
       ParseFrame::Footnote{id} => TreeElement::Footnote{id, children},
       ParseFrame::QuoteBlock => TreeElement::QuoteBlock(children),
       ParseFrame::Tab(title) => TreeElement::Tab{title, children},
       ParseFrame::TabView => TreeElement::TabView(children),
+      ParseFrame::List{kind} => TreeElement::List{kind, children},
+      ParseFrame::ListItem => TreeElement::ListItem(children),
       ParseFrame::HtmlElement { tag, property } => TreeElement::HtmlElement { tag, property, children },
-    }
+      ParseFrame::Align(alignment) => TreeElement::Align { alignment, children },
+    })
   }
 
   pub fn get_kind(&self) -> ParseFrameKind {
@@ -169,12 +556,15 @@ impl ParseFrame {
       ParseFrame::Subscript => ParseFrameKind::Subscript,
       ParseFrame::Colored{..} => ParseFrameKind::Colored,
       ParseFrame::Size{..} => ParseFrameKind::Size,
-      ParseFrame::Collapsible => ParseFrameKind::Collapsible,
+      ParseFrame::Collapsible{..} => ParseFrameKind::Collapsible,
       ParseFrame::Footnote{..} => ParseFrameKind::Footnote,
       ParseFrame::QuoteBlock =>  ParseFrameKind::QuoteBlock,
       ParseFrame::Tab{..} => ParseFrameKind::Tab,
       ParseFrame::TabView => ParseFrameKind::TabView,
+      ParseFrame::List{..} => ParseFrameKind::List,
+      ParseFrame::ListItem => ParseFrameKind::ListItem,
       ParseFrame::HtmlElement{tag, ..} => ParseFrameKind::HtmlElement{tag: tag.clone()},
+      ParseFrame::Align(_) => ParseFrameKind::Align,
     }
   }
 }
@@ -196,5 +586,111 @@ pub enum ParseFrameKind {
   QuoteBlock,
   Tab,
   TabView,
+  List,
+  ListItem,
   HtmlElement{tag: String},
-}
\ No newline at end of file
+  Align,
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_css_size() {
+    let size = |s: &str| CssSize::new(s).map(CssSize::into_string);
+    assert_eq!(size("150%"), Some(String::from("150%")));
+    assert_eq!(size(" 1.50EM "), Some(String::from("1.5em")));
+    assert_eq!(size(".8rem"), Some(String::from("0.8rem")));
+    assert_eq!(size("12px"), Some(String::from("12px")));
+    assert_eq!(size("10pt"), Some(String::from("10pt")));
+    assert_eq!(size("XX-Large"), Some(String::from("xx-large")));
+    assert_eq!(size("smaller"), Some(String::from("smaller")));
+    assert_eq!(CssSize::new("80%"), Some(CssSize::Length(CssLength::new(80.0, CssUnit::Percent).unwrap())));
+
+    for s in ["", "12", "px", "-1em", "1e3px", "1.2.3em", "infpx", "12 px", "1em; color: red", "1em}body{x", "calc(1em)", "huge"] {
+      assert_eq!(CssSize::new(s), None, "{}", s);
+    }
+    for v in [f64::NAN, f64::INFINITY, -1.0] {
+      assert_eq!(CssLength::new(v, CssUnit::Em), None, "{}", v);
+    }
+  }
+
+  #[cfg(feature = "serde")]
+  fn round_trip(ast: &[TreeElement]) {
+    let json = serde_json::to_string(ast).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<TreeElement>>(&json).unwrap(), ast, "{}", json);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_json_shape() {
+    let ast = crate::parse_to_ast(String::from("**a**\nb")).unwrap();
+    assert_eq!(serde_json::to_value(&ast).unwrap(), serde_json::json!([
+      {"type": "paragraph", "value": [
+        {"type": "bold", "value": [{"type": "text", "value": "a"}]},
+        {"type": "new_line"},
+        {"type": "text", "value": "b"},
+      ]},
+    ]));
+
+    let ast = vec![
+      TreeElement::Size { scale: CssSize::new("80%").unwrap(), children: vec![] },
+      TreeElement::Link { href: Url(String::from("https://example.com")), open_in_new_tab: true, name: String::from("x") },
+      TreeElement::List { kind: ListKind::Ordered, children: vec![] },
+      TreeElement::Table(vec![vec![table_cell::Cell { val: vec![], style: Some(table_cell::Style::LeftAligned), spanning: std::num::NonZeroUsize::MIN }]]),
+    ];
+    assert_eq!(serde_json::to_value(&ast).unwrap(), serde_json::json!([
+      {"type": "size", "value": {"scale": "80%", "children": []}},
+      {"type": "link", "value": {"href": "https://example.com", "open_in_new_tab": true, "name": "x"}},
+      {"type": "list", "value": {"kind": "ordered", "children": []}},
+      {"type": "table", "value": [[{"val": [], "style": "left_aligned", "spanning": 1}]]},
+    ]));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_round_trip_parsed() {
+    let pages = [
+      "+ Title\n++* Hidden\n**b** //i// __u__ --s-- {{m}} ^^sup^^ ,,sub,,",
+      "##red|colored## ##ff8800|hex## [[span class=\"x\"]]span[[/span]]",
+      "[https://example.com link] [[[page|name]]] [[[*new]]]",
+      "text[[footnote]]note[[/footnote]]\n[[footnoteblock title=\"Notes\"]]",
+      "> quote\n>> nested\n* a\n** b\n# c",
+      "||~ a ||< b ||\n|||| c ||",
+      "[[code type=\"rust\"]]\nfn main() {}\n[[/code]]",
+      "[[tabview]]\n[[tab One]]\na\n[[/tab]]\n[[/tabview]]",
+      "[[collapsible show=\"+ Open\" hide=\"- Close\" folded=\"no\" hideLocation=\"both\"]]\na\n[[/collapsible]]",
+      "[[div class=\"box\"]]\na\n[[/div]]",
+      ": term : **definition**\n: a : b",
+      "[[size 150%]]big[[/size]] [[size x-small]]small[[/size]]",
+    ];
+    for page in pages {
+      round_trip(&crate::parse_to_ast(String::from(page)).unwrap());
+    }
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_round_trip_constructed() {
+    round_trip(&[
+      TreeElement::Size { scale: CssSize::new("1.5em").unwrap(), children: vec![TreeElement::Text(String::from("big"))] },
+      TreeElement::Iframe(String::from("<p>raw</p>")),
+      TreeElement::Collapsible { show: String::from("s"), hide: String::from("h"), folded: true, hide_location: HideLocation::Neither, children: vec![] },
+      TreeElement::FootnoteBlock { title: None },
+      TreeElement::CodeBlock { language: None, source: String::from("<&>") },
+    ]);
+
+    for color in [WikidotColor::Aqua, WikidotColor::Fuchsia, WikidotColor::Yellow] {
+      let json = serde_json::to_string(&color).unwrap();
+      assert_eq!(WikidotColor::from(json.trim_matches('"')), Some(color));
+      assert_eq!(serde_json::from_str::<WikidotColor>(&json).unwrap(), color);
+    }
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_invalid_css_size() {
+    let json = r#"{"type": "size", "value": {"scale": "1em; background: red", "children": []}}"#;
+    assert!(serde_json::from_str::<TreeElement>(json).is_err());
+  }
+}
@@ -1,5 +1,8 @@
 use crate::tokenizer::Token;
-use crate::ast::table_cell;
+use crate::ast::{table_cell, Float, HideLocation, ListKind, TextAlign};
+use crate::error::ParseError;
+use crate::lint::{Warning, WarningKind};
+use crate::span::{Span, SpanTree};
 
 mod parse_table;
 mod data_builder;
@@ -10,6 +13,18 @@ pub enum BlockLevelAttribute {
   Table(Vec<Vec<table_cell::BlockCell>>), // Inline以外中には入らないようにする必要がある.
   TabView(Vec<BlockLevelAttribute>), // only contains Tabs
   Tab{title: String, children: Vec<BlockLevelAttribute>},
+  Heading{level: u8, in_toc: bool, children: Vec<crate::tokenizer::Token>}, // 1行で終わる
+  List{kind: ListKind, children: Vec<BlockLevelAttribute>}, // only contains ListItems
+  CodeBlock{language: Option<String>, source: String},
+  Div{property: Vec<(String, String)>, children: Vec<BlockLevelAttribute>},
+  FootnoteBlock{title: Option<String>},
+  TableOfContents{float: Option<Float>},
+  HorizontalRule,
+  ClearFloat(Option<Float>),
+  Collapsible{show: String, hide: String, folded: bool, hide_location: HideLocation, children: Vec<BlockLevelAttribute>},
+  Align{alignment: TextAlign, children: Vec<BlockLevelAttribute>},
+  DefinitionList(Vec<(Vec<crate::tokenizer::Token>, Vec<crate::tokenizer::Token>)>), // 続く`: 用語 : 説明`の行をまとめる
+  ListItem(Vec<BlockLevelAttribute>),
 
   Inline(Vec<crate::tokenizer::Token>), // トップレベルのInlineは段落を示す.
 }
@@ -18,31 +33,184 @@ pub enum BlockLevelFrame {
   BlockQuote,
   TabView,
   Tab{title: String},
+  List{kind: ListKind},
+  ListItem,
+  Div{property: Vec<(String, String)>},
+  Collapsible{show: String, hide: String, folded: bool, hide_location: HideLocation},
+  Align(TextAlign),
   // Table ... trailing element
   // Inline ... trailing element
 }
 
+impl BlockLevelFrame {
+  /// `name` of frames opened by a `[[name]]` tag.
+  fn element_name(&self) -> Option<&'static str> {
+    match self {
+      BlockLevelFrame::TabView => Some("tabview"),
+      BlockLevelFrame::Tab { .. } => Some("tab"),
+      BlockLevelFrame::Div { .. } => Some("div"),
+      BlockLevelFrame::Collapsible { .. } => Some("collapsible"),
+      BlockLevelFrame::Align(alignment) => Some(alignment.element_name()),
+      BlockLevelFrame::BlockQuote | BlockLevelFrame::List { .. } | BlockLevelFrame::ListItem => None,
+    }
+  }
+}
+
+
+/// `[[collapsible show="+ Open" hide="- Close" folded="no" hideLocation="both"]]`
+fn collapsible_frame(attributes: &[(String, String)]) -> BlockLevelFrame {
+  let get = |key: &str| attributes.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.clone());
+
+  BlockLevelFrame::Collapsible {
+    show: get("show").unwrap_or(String::from("+ show block")),
+    hide: get("hide").unwrap_or(String::from("- hide block")),
+    folded: get("folded").is_none_or(|v| v != "no"),
+    hide_location: get("hideLocation").and_then(|v| HideLocation::from(&v)).unwrap_or(HideLocation::Top),
+  }
+}
+
+/// `[[name]]` and `[[/name]]` tags that the block parser reads, and `[[code]]`.
+fn is_block_element(token: &Token) -> bool {
+  match token {
+    Token::ElementBegin { name, .. } => matches!(name.as_str(),
+      "tabview" | "tab" | "div" | "collapsible" | "<" | ">" | "=" | "==" | "footnoteblock" | "toc" | "f<toc" | "f>toc" | "clearfloat"),
+    Token::ElementEnd(name) => matches!(name.as_str(), "tabview" | "tab" | "div" | "collapsible" | "<" | ">" | "=" | "=="),
+    Token::Code { .. } => true,
+    _ => false,
+  }
+}
+
+/// Splits spanned tokens into the tokens and a `SpanTree` whose children are the tokens' spans.
+fn tokens_with_spans(tokens: Vec<(Token, Span)>) -> (Vec<Token>, SpanTree) {
+  let (tokens, spans): (Vec<Token>, Vec<Span>) = tokens.into_iter().unzip();
+  let children: Vec<SpanTree> = spans.into_iter().map(SpanTree::leaf).collect();
+  let spans = SpanTree::covering(children.clone()).unwrap_or(SpanTree { span: Span::default(), children });
+  (tokens, spans)
+}
 
-pub fn parse(tokens: Vec<crate::tokenizer::Token>) -> Vec<BlockLevelAttribute> {
-  let mut db = data_builder::DataBuilder::new();
+/// Returns the blocks and, in parallel, their `SpanTree`s. The children of a block's tree are its child blocks,
+/// the tokens of `Inline` and `Heading`, the cells of `Table`, or the items of `DefinitionList`,
+/// each holding the tokens of its term and of its definition.
+pub fn parse(tokens: Vec<(crate::tokenizer::Token, Span)>, warnings: &mut Vec<Warning>) -> Result<(Vec<BlockLevelAttribute>, Vec<SpanTree>), ParseError> {
+  let mut db = data_builder::DataBuilder::new(warnings);
 
   let mut is_last_newline = false;
-  for token in tokens {
+  let mut heading: Option<PendingHeading> = None; // 行末まで見出しの中身を集める
+  let mut definition: Option<PendingDefinition> = None; // 行末まで用語と説明を集める
+  let mut definitions = vec![]; // 読み終えた定義の行
+  for (token, span) in tokens {
+    db.set_span(span);
+
+    if !definitions.is_empty() && is_last_newline && token != Token::DefinitionTerm {
+      add_definition_list(&mut db, std::mem::take(&mut definitions));
+    }
+
+    // 見出し, 定義の行とリスト項目は1行で終わり, 中にブロック要素を置けないので, その前で終える
+    if is_block_element(&token) {
+      if let Some(v) = heading.take() {
+        add_heading(&mut db, v);
+      }
+      definitions.extend(definition.take());
+      if !definitions.is_empty() {
+        add_definition_list(&mut db, std::mem::take(&mut definitions));
+      }
+      db.close_lists();
+    }
+
+    if let Some(PendingHeading { children, .. }) = &mut heading && token != Token::NewLine {
+      children.push((token, span));
+      continue;
+    }
+
+    if let Some(pending) = &mut definition && token != Token::NewLine {
+      pending.span = pending.span.merge(span);
+      if token == Token::DefinitionSeparator && pending.definition.is_none() {
+        pending.definition = Some(vec![]);
+      } else if let Some(children) = &mut pending.definition {
+        children.push((token, span));
+      } else {
+        pending.term.push((token, span));
+      }
+      continue;
+    }
+
     match token {
+      Token::Heading { level, in_toc } => {
+        if is_last_newline {
+          db.close_lists();
+          db.set_bq_depth(0)?;
+        }
+
+        db.flush();
+        heading = Some(PendingHeading { level, in_toc, span, children: vec![] });
+
+        is_last_newline = false;
+      }
+
+      Token::DefinitionTerm => {
+        if is_last_newline {
+          db.close_lists();
+          db.set_bq_depth(0)?;
+        }
+
+        db.flush();
+        definition = Some(PendingDefinition { span, term: vec![], definition: None });
+
+        is_last_newline = false;
+      }
+
       Token::BlockQuote(level) => {
-        db.set_bq_depth(level.get());
+        db.close_lists();
+        db.set_bq_depth(level.get())?;
+
+        is_last_newline = false;
+      }
+
+      Token::ListItem { kind, depth } => {
+        if is_last_newline {
+          db.set_bq_depth(0)?;
+        }
+
+        db.set_list_item(kind, depth.get())?;
+
+        is_last_newline = false;
+      }
+
+      Token::Code { language, source } => {
+        if is_last_newline {
+          db.close_lists();
+          db.set_bq_depth(0)?;
+        }
+
+        db.add(BlockLevelAttribute::CodeBlock { language, source }, SpanTree::leaf(span));
+
+        is_last_newline = false;
+      }
+
+      Token::HorizontalRule | Token::ClearFloat(_) => {
+        if is_last_newline {
+          db.close_lists();
+          db.set_bq_depth(0)?;
+        }
+
+        let block = match token {
+          Token::ClearFloat(float) => BlockLevelAttribute::ClearFloat(float),
+          _ => BlockLevelAttribute::HorizontalRule,
+        };
+        db.add(block, SpanTree::leaf(span));
 
         is_last_newline = false;
       }
 
       Token::ElementBegin { ref name, ref attributes } => {
         if is_last_newline {
-          db.set_bq_depth(0);
+          db.close_lists();
+          db.set_bq_depth(0)?;
         }
 
         match name.as_str() {
           "tabview" => {
-            db.push(BlockLevelFrame::TabView);
+            db.push(BlockLevelFrame::TabView)?;
           }
 
           "tab" => {
@@ -56,11 +224,43 @@ pub fn parse(tokens: Vec<crate::tokenizer::Token>) -> Vec<BlockLevelAttribute> {
               }
             }
 
-            db.push(BlockLevelFrame::Tab { title });
+            db.push(BlockLevelFrame::Tab { title })?;
+          }
+
+          "div" => {
+            db.push(BlockLevelFrame::Div { property: attributes.clone() })?;
+          }
+
+          "collapsible" => {
+            db.push(collapsible_frame(attributes))?;
+          }
+
+          "<" | ">" | "=" | "==" => {
+            if let Some(alignment) = TextAlign::from_element_name(name) {
+              db.push(BlockLevelFrame::Align(alignment))?;
+            }
+          }
+
+          "footnoteblock" => {
+            let title = attributes.iter().find(|(k, _)| k == "title").map(|(_, v)| v.clone());
+            db.add(BlockLevelAttribute::FootnoteBlock { title }, SpanTree::leaf(span));
+          }
+
+          "toc" | "f<toc" | "f>toc" => {
+            let float = match name.as_str() {
+              "f<toc" => Some(Float::Left),
+              "f>toc" => Some(Float::Right),
+              _ => None,
+            };
+            db.add(BlockLevelAttribute::TableOfContents { float }, SpanTree::leaf(span));
+          }
+
+          "clearfloat" => {
+            db.add(BlockLevelAttribute::ClearFloat(None), SpanTree::leaf(span));
           }
 
           &_ => {
-            db.add_token(token);
+            db.add_token(token, span);
           }
         }
 
@@ -69,24 +269,20 @@ pub fn parse(tokens: Vec<crate::tokenizer::Token>) -> Vec<BlockLevelAttribute> {
 
       Token::ElementEnd(ref name) => {
         if is_last_newline {
-          db.set_bq_depth(0);
+          db.close_lists();
+          db.set_bq_depth(0)?;
         }
+        db.include_current_token(); // 閉じタグまでを要素の範囲にする
 
         match name.as_str() {
-          "tabview" => {
-            if let Some(BlockLevelFrame::TabView) = db.get_last_frame() {
-              db.pop_and_merge();
-            }
-          }
-
-          "tab" => {
-            if let Some(BlockLevelFrame::Tab { title: _ }) = db.get_last_frame() {
-              db.pop_and_merge();
+          "tabview" | "tab" | "div" | "collapsible" | "<" | ">" | "=" | "==" => {
+            if !db.close_element(name) {
+              db.warn(WarningKind::UnmatchedClose { markup: format!("[[/{}]]", name) }, span);
             }
           }
 
           &_ => {
-            db.add_token(token);
+            db.add_token(token, span);
           }
         }
 
@@ -94,11 +290,16 @@ pub fn parse(tokens: Vec<crate::tokenizer::Token>) -> Vec<BlockLevelAttribute> {
       }
 
       Token::NewLine => {
-        if is_last_newline {
+        if let Some(v) = heading.take() {
+          add_heading(&mut db, v);
+        } else if let Some(v) = definition.take() {
+          definitions.push(v);
+        } else if is_last_newline {
           db.flush();
-          db.set_bq_depth(0);
+          db.close_lists();
+          db.set_bq_depth(0)?;
         } else {
-          db.add_token(token);
+          db.add_token(token, span);
         }
 
         is_last_newline = true;
@@ -106,21 +307,71 @@ pub fn parse(tokens: Vec<crate::tokenizer::Token>) -> Vec<BlockLevelAttribute> {
 
       _ => {
         if is_last_newline {
-          db.set_bq_depth(0);
+          db.close_lists();
+          db.set_bq_depth(0)?;
         }
 
-        db.add_token(token);
+        db.add_token(token, span);
+
+        is_last_newline = false;
       }
     }
   }
 
-  db.get()
+  if let Some(v) = heading {
+    add_heading(&mut db, v);
+  }
+  definitions.extend(definition);
+  if !definitions.is_empty() {
+    add_definition_list(&mut db, definitions);
+  }
+
+  Ok(db.get())
+}
+
+struct PendingHeading {
+  level: u8,
+  in_toc: bool,
+  span: Span, // `+ `の範囲
+  children: Vec<(Token, Span)>,
+}
+
+fn add_heading(db: &mut data_builder::DataBuilder, PendingHeading { level, in_toc, span, children }: PendingHeading) {
+  let (children, mut spans) = tokens_with_spans(children);
+  spans.span = if children.is_empty() { span } else { span.merge(spans.span) };
+  db.add(BlockLevelAttribute::Heading { level, in_toc, children }, spans);
+}
+
+struct PendingDefinition {
+  span: Span, // `: `から読んだトークンまで
+  term: Vec<(Token, Span)>,
+  definition: Option<Vec<(Token, Span)>>, // ` : `を読むまではNone
+}
+
+fn add_definition_list(db: &mut data_builder::DataBuilder, definitions: Vec<PendingDefinition>) {
+  let mut items = vec![];
+  let mut spans = vec![];
+  for PendingDefinition { span, term, definition } in definitions {
+    let (term, term_spans) = tokens_with_spans(term);
+    let (definition, definition_spans) = tokens_with_spans(definition.unwrap_or_default());
+    items.push((term, definition));
+    spans.push(SpanTree { span, children: vec![term_spans, definition_spans] });
+  }
+  db.add(BlockLevelAttribute::DefinitionList(items), SpanTree::covering(spans).unwrap_or_default());
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  fn parse(tokens: Vec<Token>) -> Vec<BlockLevelAttribute> {
+    super::parse(tokens.into_iter().map(|v| (v, Span::default())).collect(), &mut vec![]).unwrap().0
+  }
+
+  fn tokenize(s: String) -> Vec<Token> {
+    crate::tokenizer::tokenize(s, &mut vec![]).into_iter().map(|(token, _)| token).collect()
+  }
+
   fn nz(v: usize) -> std::num::NonZeroUsize {
     std::num::NonZeroUsize::try_from(v).unwrap()
   }
@@ -145,6 +396,16 @@ mod tests {
     ]);
   }
 
+  #[test]
+  fn test_multiline_paragraph() {
+    assert_eq!(parse(tokenize(sf("a\nb\nc\n\nd"))), vec![
+      BlockLevelAttribute::Inline(vec![
+        Token::Text(sf("a")), Token::NewLine, Token::Text(sf("b")), Token::NewLine, Token::Text(sf("c")),
+      ]),
+      BlockLevelAttribute::Inline(vec![Token::Text(sf("d"))]),
+    ]);
+  }
+
   #[test]
   fn test_blockquote() {
     use crate::tokenizer::Token;
@@ -163,7 +424,6 @@ mod tests {
 
   #[test]
   fn test_table_in_blocks() {
-    use crate::tokenizer::tokenize;
     use crate::tokenizer::Token;
     // "a\n|| a || b ||\nc"
     let tokens = tokenize(String::from("a\n|| a || b ||\nc"));
@@ -208,6 +468,128 @@ mod tests {
     ]);
   }
 
+  #[test]
+  fn test_heading() {
+    use crate::tokenizer::Token;
+
+    let parsed = parse(tokenize(String::from("para\n+ Title **bold**\n> quote\n++* Hidden")));
+
+    assert_eq!(parsed, vec![
+      BlockLevelAttribute::Inline(vec![Token::Text(String::from("para"))]),
+      BlockLevelAttribute::Heading { level: 1, in_toc: true, children: vec![
+        Token::Text(String::from("Title ")), Token::Bold, Token::Text(String::from("bold")), Token::Bold,
+      ] },
+      BlockLevelAttribute::BlockQuote(vec![
+        BlockLevelAttribute::Inline(vec![Token::Text(String::from("quote"))]),
+      ]),
+      BlockLevelAttribute::Heading { level: 2, in_toc: false, children: vec![Token::Text(String::from("Hidden"))] },
+    ]);
+    // ブロック要素の前で見出しは終わる
+    assert_eq!(parse(tokenize(String::from("+ a [[div]]\nb\n[[/div]]"))), vec![
+      BlockLevelAttribute::Heading { level: 1, in_toc: true, children: vec![Token::Text(String::from("a "))] },
+      BlockLevelAttribute::Div { property: vec![], children: vec![BlockLevelAttribute::Inline(vec![Token::Text(String::from("b"))])] },
+    ]);
+  }
+
+  #[test]
+  fn test_list() {
+    use crate::tokenizer::Token;
+
+    let parsed = parse(tokenize(String::from("* a\n * a-1\n  # a-1-i\n * a-2\n* b\n# c\nd")));
+
+    let item = |s: &str| BlockLevelAttribute::Inline(vec![Token::Text(String::from(s))]);
+
+    assert_eq!(parsed, vec![
+      BlockLevelAttribute::List { kind: ListKind::Unordered, children: vec![
+        BlockLevelAttribute::ListItem(vec![
+          item("a"),
+          BlockLevelAttribute::List { kind: ListKind::Unordered, children: vec![
+            BlockLevelAttribute::ListItem(vec![
+              item("a-1"),
+              BlockLevelAttribute::List { kind: ListKind::Ordered, children: vec![
+                BlockLevelAttribute::ListItem(vec![item("a-1-i")]),
+              ] },
+            ]),
+            BlockLevelAttribute::ListItem(vec![item("a-2")]),
+          ] },
+        ]),
+        BlockLevelAttribute::ListItem(vec![item("b")]),
+      ] },
+      BlockLevelAttribute::List { kind: ListKind::Ordered, children: vec![
+        BlockLevelAttribute::ListItem(vec![item("c")]),
+      ] },
+      item("d"),
+    ]);
+  }
+
+  #[test]
+  fn test_block_element_after_list_item() {
+    use crate::tokenizer::Token;
+
+    let parsed = parse(tokenize(String::from("# a[[=]]\nb\n[[/=]]\n: c : d[[div]]\ne\n[[/div]]")));
+
+    let text = |s: &str| BlockLevelAttribute::Inline(vec![Token::Text(String::from(s))]);
+
+    assert_eq!(parsed, vec![
+      BlockLevelAttribute::List { kind: ListKind::Ordered, children: vec![
+        BlockLevelAttribute::ListItem(vec![text("a")]),
+      ] },
+      BlockLevelAttribute::Align { alignment: TextAlign::Center, children: vec![text("b")] },
+      BlockLevelAttribute::DefinitionList(vec![
+        (vec![Token::Text(String::from("c"))], vec![Token::Text(String::from("d"))]),
+      ]),
+      BlockLevelAttribute::Div { property: vec![], children: vec![text("e")] },
+    ]);
+  }
+
+  #[test]
+  fn test_code_block() {
+    use crate::tokenizer::Token;
+
+    let parsed = parse(tokenize(String::from("a\n[[code]]\n**x**\n[[/code]]\nb")));
+
+    assert_eq!(parsed, vec![
+      BlockLevelAttribute::Inline(vec![Token::Text(String::from("a"))]),
+      BlockLevelAttribute::CodeBlock { language: None, source: String::from("**x**") },
+      BlockLevelAttribute::Inline(vec![Token::Text(String::from("b"))]),
+    ]);
+  }
+
+  #[test]
+  fn test_div() {
+    use crate::tokenizer::Token;
+
+    let parsed = parse(tokenize(String::from("[[div class=\"box\"]]\na\n\n[[span]]b[[/span]]\n[[/div]]\nc")));
+
+    assert_eq!(parsed, vec![
+      BlockLevelAttribute::Div { property: vec![(String::from("class"), String::from("box"))], children: vec![
+        BlockLevelAttribute::Inline(vec![Token::Text(String::from("a"))]),
+        BlockLevelAttribute::Inline(vec![
+          Token::ElementBegin { name: String::from("span"), attributes: vec![] },
+          Token::Text(String::from("b")),
+          Token::ElementEnd(String::from("span")),
+        ]),
+      ] },
+      BlockLevelAttribute::Inline(vec![Token::Text(String::from("c"))]),
+    ]);
+  }
+
+  #[test]
+  fn test_collapsible() {
+    use crate::tokenizer::Token;
+
+    let parsed = parse(tokenize(String::from("[[collapsible show=\"+ Open\" hide=\"- Close\" folded=\"no\" hideLocation=\"both\"]]\n> a\n[[/collapsible]]\n[[collapsible]]\nb\n[[/collapsible]]")));
+
+    assert_eq!(parsed, vec![
+      BlockLevelAttribute::Collapsible { show: sf("+ Open"), hide: sf("- Close"), folded: false, hide_location: HideLocation::Both, children: vec![
+        BlockLevelAttribute::BlockQuote(vec![BlockLevelAttribute::Inline(vec![Token::Text(sf("a"))])]),
+      ] },
+      BlockLevelAttribute::Collapsible { show: sf("+ show block"), hide: sf("- hide block"), folded: true, hide_location: HideLocation::Top, children: vec![
+        BlockLevelAttribute::Inline(vec![Token::Text(sf("b"))]),
+      ] },
+    ]);
+  }
+
   #[test]
   fn test_tabview() {
     use crate::tokenizer::Token;
@@ -1,47 +1,82 @@
 use crate::tokenizer::Token;
+use crate::span::{Position, Span, SpanTree};
+
+use crate::ast::ListKind;
+use crate::error::{ParseError, MAX_NESTING_DEPTH};
+use crate::lint::{Warning, WarningKind};
 
 use super::{BlockLevelAttribute, BlockLevelFrame};
 
-pub struct DataBuilder {
+pub struct DataBuilder<'w> {
   root: Vec<BlockLevelAttribute>,
   data: Vec<(BlockLevelFrame, Vec<BlockLevelAttribute>)>,
-  buf: Vec<Token>,
-  blockquote_depth_count: usize
+  root_spans: Vec<SpanTree>,
+  spans: Vec<(Span, Vec<SpanTree>)>, // dataと同じ深さで, 開いたトークンの範囲と子のSpanTreeを持つ
+  buf: Vec<(Token, Span)>,
+  blockquote_depth_count: usize,
+  list_depth_count: usize,
+  span: Span, // 今読んでいるトークン
+  last_end: Position, // 読み終えたトークンの終わり. 閉じた要素はここまでとする
+  is_closing_by_tag: bool,
+  warnings: &'w mut Vec<Warning>,
 }
 
-impl DataBuilder {
-  pub fn new() -> Self {
+impl<'w> DataBuilder<'w> {
+  pub fn new(warnings: &'w mut Vec<Warning>) -> Self {
     Self {
       root: vec![],
       data: vec![],
+      root_spans: vec![],
+      spans: vec![],
       buf: vec![],
-      blockquote_depth_count: 0
+      blockquote_depth_count: 0,
+      list_depth_count: 0,
+      span: Span::default(),
+      last_end: Position::default(),
+      is_closing_by_tag: false,
+      warnings,
     }
   }
 
-  fn pop(&mut self) -> Option<(BlockLevelFrame, Vec<BlockLevelAttribute>)> {
-    let v = self.data.pop();
-    if let Some(val) = &v && let BlockLevelFrame::BlockQuote = val.0 {
+  pub fn warn(&mut self, kind: WarningKind, span: Span) {
+    self.warnings.push(Warning { kind, span });
+  }
+
+  /// Called for every token before it is handled.
+  pub fn set_span(&mut self, span: Span) {
+    self.last_end = self.span.end;
+    self.span = span;
+  }
+
+  /// Makes frames closed while handling the current token end after it.
+  pub fn include_current_token(&mut self) {
+    self.last_end = self.span.end;
+  }
+
+  fn pop(&mut self) -> Option<(BlockLevelFrame, Vec<BlockLevelAttribute>, SpanTree)> {
+    let (frame, children) = self.data.pop()?;
+    let (opener, span_children) = self.spans.pop().unwrap_or_default();
+    let span_tree = SpanTree { span: Span { start: opener.start, end: self.last_end.max(opener.start) }, children: span_children };
+    if let Some(name) = frame.element_name() && !self.is_closing_by_tag {
+      self.warn(WarningKind::Unclosed { markup: format!("[[{}]]", name) }, opener);
+    }
+    if let BlockLevelFrame::BlockQuote = frame {
       self.blockquote_depth_count-=1;
     };
+    if let BlockLevelFrame::List { .. } = frame {
+      self.list_depth_count-=1;
+    };
 
-    v
+    Some((frame, children, span_tree))
   }
 
   pub fn flush(&mut self) {
-    while let Some(Token::NewLine) = self.buf.last() {
+    while let Some((Token::NewLine, _)) = self.buf.last() {
       self.buf.pop();
     }
     if !self.buf.is_empty() {
-      let target: &mut Vec<BlockLevelAttribute>;
-      if let Some(pt) = self.data.last_mut() {
-        target = &mut pt.1;
-      } else {
-        target = &mut self.root;
-      }
-
-      for v in super::parse_table::parse_table(&mut self.buf) {
-        target.push(v);
+      for (v, span_tree) in super::parse_table::parse_table(&mut self.buf) {
+        self.add_without_flush(v, span_tree);
       }
     }
   }
@@ -49,54 +84,87 @@ impl DataBuilder {
   pub fn pop_and_merge(&mut self) -> bool {
     self.flush();
 
-    if let Some((now_frame, now_children)) = self.pop() {
-      let push_target: &mut Vec<BlockLevelAttribute>;
-      if let Some(ar) = self.data.last_mut() {
-        push_target = &mut ar.1;
-      } else {
-        push_target = &mut self.root;
-      }
-
-      match now_frame {
+    if let Some((now_frame, now_children, span_tree)) = self.pop() {
+      let attribute = match now_frame {
         BlockLevelFrame::BlockQuote => {
-          push_target.push(BlockLevelAttribute::BlockQuote(now_children));
+          BlockLevelAttribute::BlockQuote(now_children)
         }
 
         BlockLevelFrame::TabView => {
-          push_target.push(BlockLevelAttribute::TabView(now_children));
+          BlockLevelAttribute::TabView(now_children)
         }
 
         BlockLevelFrame::Tab { title } => {
-          push_target.push(BlockLevelAttribute::Tab { title, children: now_children });
+          BlockLevelAttribute::Tab { title, children: now_children }
         }
-      }
+
+        BlockLevelFrame::List { kind } => {
+          BlockLevelAttribute::List { kind, children: now_children }
+        }
+
+        BlockLevelFrame::ListItem => {
+          BlockLevelAttribute::ListItem(now_children)
+        }
+
+        BlockLevelFrame::Div { property } => {
+          BlockLevelAttribute::Div { property, children: now_children }
+        }
+
+        BlockLevelFrame::Collapsible { show, hide, folded, hide_location } => {
+          BlockLevelAttribute::Collapsible { show, hide, folded, hide_location, children: now_children }
+        }
+
+        BlockLevelFrame::Align(alignment) => {
+          BlockLevelAttribute::Align { alignment, children: now_children }
+        }
+      };
+      self.add_without_flush(attribute, span_tree);
       true
     } else {
       false
     }
   }
 
-  pub fn push(&mut self, frame: BlockLevelFrame) {
+  pub fn push(&mut self, frame: BlockLevelFrame) -> Result<(), ParseError> {
+    if self.data.len() >= MAX_NESTING_DEPTH {
+      return Err(ParseError::NestingTooDeep { limit: MAX_NESTING_DEPTH, position: self.span.start });
+    }
     self.flush();
 
     if let BlockLevelFrame::BlockQuote = &frame {
       self.blockquote_depth_count+=1;
     }
+    if let BlockLevelFrame::List { .. } = &frame {
+      self.list_depth_count+=1;
+    }
     self.data.push((frame, vec![]));
+    self.spans.push((self.span, vec![]));
+    Ok(())
   }
 
-  pub fn add(&mut self, data: BlockLevelAttribute) {
+  pub fn add(&mut self, data: BlockLevelAttribute, span_tree: SpanTree) {
     self.flush();
+    self.add_without_flush(data, span_tree);
+  }
 
+  fn add_without_flush(&mut self, data: BlockLevelAttribute, span_tree: SpanTree) {
     if let Some((_, target)) = self.data.last_mut() {
       target.push(data);
+    } else {
+      self.root.push(data);
+    }
+
+    if let Some((_, target)) = self.spans.last_mut() {
+      target.push(span_tree);
+    } else {
+      self.root_spans.push(span_tree);
     }
   }
 
-  pub fn add_token(&mut self, token: Token) {
-    if self.buf.last().is_none_or(|v| *v == Token::NewLine) && token == Token::NewLine {
+  pub fn add_token(&mut self, token: Token, span: Span) {
+    if self.buf.last().is_none_or(|(v, _)| *v == Token::NewLine) && token == Token::NewLine {
     } else {
-      self.buf.push(token);
+      self.buf.push((token, span));
     }
   }
 
@@ -104,7 +172,7 @@ impl DataBuilder {
     self.data.is_empty()
   }
 
-  pub fn set_bq_depth(&mut self, depth: usize) {
+  pub fn set_bq_depth(&mut self, depth: usize) -> Result<(), ParseError> {
     if self.blockquote_depth_count > depth {
       while self.blockquote_depth_count > depth && !self.stack_is_empty() {
         self.pop_and_merge();
@@ -113,30 +181,67 @@ impl DataBuilder {
 
     if self.blockquote_depth_count < depth {
       while self.blockquote_depth_count < depth {
-        self.push(BlockLevelFrame::BlockQuote);
+        self.push(BlockLevelFrame::BlockQuote)?;
       }
     }
+    Ok(())
   }
 
-  pub fn get_last_frame(&self) -> Option<&BlockLevelFrame> {
-    if let Some(v) = self.data.last() {
-      Some(&v.0)
-    } else {
-      None
+  /// Starts a new list item at the given depth, closing or opening lists as needed.
+  pub fn set_list_item(&mut self, kind: ListKind, depth: usize) -> Result<(), ParseError> {
+    while self.list_depth_count > depth && !self.stack_is_empty() {
+      self.pop_and_merge();
     }
+
+    if self.list_depth_count == depth {
+      if let Some(BlockLevelFrame::ListItem) = self.get_last_frame() {
+        self.pop_and_merge();
+      }
+      if let Some(BlockLevelFrame::List { kind: last_kind }) = self.get_last_frame() && *last_kind != kind {
+        self.pop_and_merge(); // kind changed: begin another list
+      }
+    }
+
+    while self.list_depth_count < depth {
+      if let Some(BlockLevelFrame::List { .. }) = self.get_last_frame() {
+        self.push(BlockLevelFrame::ListItem)?;
+      }
+      self.push(BlockLevelFrame::List { kind })?;
+    }
+
+    self.push(BlockLevelFrame::ListItem)
+  }
+
+  /// Closes the innermost frame with a `[[/name]]` tag. Returns `false` if that frame is not a `[[name]]` element.
+  pub fn close_element(&mut self, name: &str) -> bool {
+    if self.get_last_frame().and_then(BlockLevelFrame::element_name) != Some(name) {
+      return false;
+    }
+
+    self.is_closing_by_tag = true;
+    self.pop_and_merge();
+    self.is_closing_by_tag = false;
+    true
   }
 
-  pub fn get_last_frame_mut(&mut self) -> Option<&mut BlockLevelFrame> {
-    if let Some(v) = self.data.last_mut() {
-      Some(&mut v.0)
+  pub fn close_lists(&mut self) {
+    while self.list_depth_count > 0 && !self.stack_is_empty() {
+      self.pop_and_merge();
+    }
+  }
+
+  pub fn get_last_frame(&self) -> Option<&BlockLevelFrame> {
+    if let Some(v) = self.data.last() {
+      Some(&v.0)
     } else {
       None
     }
   }
 
-  pub fn get(mut self) -> Vec<BlockLevelAttribute> {
+  pub fn get(mut self) -> (Vec<BlockLevelAttribute>, Vec<SpanTree>) {
+    self.include_current_token();
     self.flush();
     while self.pop_and_merge() {}
-    self.root
+    (self.root, self.root_spans)
   }
 }
\ No newline at end of file
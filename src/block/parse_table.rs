@@ -1,17 +1,24 @@
-pub fn parse_table(buf: &mut Vec<crate::tokenizer::Token>) -> Vec<super::BlockLevelAttribute> {
-  use crate::tokenizer::Token;
+use crate::span::{Span, SpanTree};
+use crate::tokenizer::Token;
+
+use super::{tokens_with_spans, BlockLevelAttribute};
+
+/// Splits a run of lines into paragraphs and tables. Each result comes with its `SpanTree`;
+/// a table's tree has one child per cell, row by row.
+pub fn parse_table(buf: &mut Vec<(Token, Span)>) -> Vec<(BlockLevelAttribute, SpanTree)> {
   let buf = std::mem::take(buf);
 
-  let mut res: Vec<super::BlockLevelAttribute> = vec![];
+  let mut res: Vec<(BlockLevelAttribute, SpanTree)> = vec![];
 
   let mut table: Vec<Vec<super::table_cell::BlockCell>> = vec![];
+  let mut table_spans = TableSpans::default();
   let mut spanning_count = 0;
   let mut is_table_line = false;
-  let mut now_buf: Vec<crate::tokenizer::Token> = vec![];
+  let mut now_buf: Vec<(Token, Span)> = vec![];
   let mut is_last_newline = true;
   let mut recent_cell_style: Option<super::table_cell::Style> = None;
 
-  for token in buf {
+  for (token, span) in buf {
     match token {
       Token::CellSeparator(v) => {
         if is_last_newline {
@@ -19,26 +26,32 @@ pub fn parse_table(buf: &mut Vec<crate::tokenizer::Token>) -> Vec<super::BlockLe
 
           if table.is_empty() && !now_buf.is_empty() && is_last_newline { // 前までの要素を書き出す
             // 改行が入っているので除去する
-            now_buf.pop().unwrap();
+            now_buf.pop();
 
-            res.push(super::BlockLevelAttribute::Inline(std::mem::take(&mut now_buf)));
+            res.push(inline(std::mem::take(&mut now_buf)));
           }
 
           table.push(vec![]);
         }
 
         if is_table_line {
+          table_spans.extend(span);
+
           // 左にセルがあるならそれを書き出す.
           // もし直前がセパレータならnow_bufは空であるから, Spanningを消して良い
           if !now_buf.is_empty() && !is_last_newline {
-            table.last_mut().unwrap().push(super::table_cell::BlockCell {
-              val: std::mem::take(&mut now_buf),
-              style: recent_cell_style,
-              spanning: spanning_count.try_into().unwrap(),
-            });
+            if let Some(row) = table.last_mut() {
+              let (val, cell_spans) = tokens_with_spans(std::mem::take(&mut now_buf));
+              row.push(super::table_cell::BlockCell {
+                val,
+                style: recent_cell_style,
+                spanning: std::num::NonZeroUsize::new(spanning_count).unwrap_or(std::num::NonZeroUsize::MIN),
+              });
+              table_spans.cells.push(cell_spans);
+            }
             spanning_count = 0;
           } else if !now_buf.is_empty() && is_last_newline {
-            res.push(super::BlockLevelAttribute::Inline(std::mem::take(&mut now_buf)));
+            res.push(inline(std::mem::take(&mut now_buf)));
           }
 
           recent_cell_style = v;
@@ -58,17 +71,16 @@ pub fn parse_table(buf: &mut Vec<crate::tokenizer::Token>) -> Vec<super::BlockLe
           }
 
           // バッファに書き込む
-          if !now_buf.is_empty() {
-            let v = now_buf.pop().unwrap();
+          if let Some((v, last_span)) = now_buf.pop() {
             if let Token::Text(mut st) = v {
               st.push_str(&txt);
-              now_buf.push(Token::Text(st))
+              now_buf.push((Token::Text(st), last_span.merge(span)))
             } else {
-              now_buf.push(v);
-              now_buf.push(Token::Text(txt));
+              now_buf.push((v, last_span));
+              now_buf.push((Token::Text(txt), span));
             }
           } else {
-            now_buf.push(Token::Text(txt));
+            now_buf.push((Token::Text(txt), span));
           }
         }
 
@@ -80,10 +92,10 @@ pub fn parse_table(buf: &mut Vec<crate::tokenizer::Token>) -> Vec<super::BlockLe
         if !is_table_line { // 通常処理であるか
           if is_last_newline { // ブロック終了
             if !now_buf.is_empty() {
-              res.push(super::BlockLevelAttribute::Inline(std::mem::take(&mut now_buf)));
+              res.push(inline(std::mem::take(&mut now_buf)));
             }
           } else { // ブロック内改行
-            now_buf.push(Token::NewLine);
+            now_buf.push((Token::NewLine, span));
           }
         } else { // 直前行がテーブル
           // テーブルの末尾をクリア
@@ -98,12 +110,15 @@ pub fn parse_table(buf: &mut Vec<crate::tokenizer::Token>) -> Vec<super::BlockLe
       _ => {
         if !table.is_empty() && !is_table_line {
           // テーブル終了処理
-          res.push(super::BlockLevelAttribute::Table(std::mem::take(&mut table)));
+          res.push((BlockLevelAttribute::Table(std::mem::take(&mut table)), std::mem::take(&mut table_spans).into_tree()));
           now_buf.clear();
           spanning_count = 0;
         }
 
-        now_buf.push(token);
+        if is_table_line {
+          table_spans.extend(span);
+        }
+        now_buf.push((token, span));
 
         is_last_newline = false;
       }
@@ -113,19 +128,45 @@ pub fn parse_table(buf: &mut Vec<crate::tokenizer::Token>) -> Vec<super::BlockLe
   // flush
   if !table.is_empty() { // table mode
     now_buf.clear();
-    res.push(super::BlockLevelAttribute::Table(std::mem::take(&mut table)));
+    res.push((BlockLevelAttribute::Table(std::mem::take(&mut table)), table_spans.into_tree()));
   } else if !now_buf.is_empty() { // normal mode
-    res.push(super::BlockLevelAttribute::Inline(std::mem::take(&mut now_buf)));
+    res.push(inline(std::mem::take(&mut now_buf)));
   }
 
   res
 }
 
+fn inline(tokens: Vec<(Token, Span)>) -> (BlockLevelAttribute, SpanTree) {
+  let (tokens, spans) = tokens_with_spans(tokens);
+  (BlockLevelAttribute::Inline(tokens), spans)
+}
+
+#[derive(Default)]
+struct TableSpans {
+  span: Option<Span>, // 最初の||から表の最後のトークンまで
+  cells: Vec<SpanTree>,
+}
+
+impl TableSpans {
+  fn extend(&mut self, span: Span) {
+    self.span = Some(self.span.map_or(span, |v| v.merge(span)));
+  }
+
+  fn into_tree(self) -> SpanTree {
+    SpanTree { span: self.span.unwrap_or_default(), children: self.cells }
+  }
+}
+
+#[cfg(test)]
 mod tests {
   use crate::{block::{BlockLevelAttribute, table_cell::{BlockCell, Style}}, tokenizer::Token};
   use super::*;
   use crate::tokenizer;
 
+  fn parse_table(buf: &mut Vec<(Token, Span)>) -> Vec<BlockLevelAttribute> {
+    super::parse_table(buf).into_iter().map(|(v, _)| v).collect()
+  }
+
   #[test]
   fn test_empty() {
     assert_eq!(parse_table(&mut vec![]), vec![]);
@@ -133,7 +174,7 @@ mod tests {
 
   #[test]
   fn test_short_text() {
-    assert_eq!(parse_table(&mut tokenizer::tokenize(String::from("Hello, World!"))), vec![
+    assert_eq!(parse_table(&mut tokenizer::tokenize(String::from("Hello, World!"), &mut vec![])), vec![
       BlockLevelAttribute::Inline(vec![
         Token::Text(String::from("Hello, World!"))
       ])
@@ -146,7 +187,7 @@ mod tests {
 
   #[test]
   fn test_table_single() {
-    assert_eq!(parse_table(&mut tokenizer::tokenize(String::from("b\n|| a || b || c ||\na"))), vec![
+    assert_eq!(parse_table(&mut tokenizer::tokenize(String::from("b\n|| a || b || c ||\na"), &mut vec![])), vec![
       BlockLevelAttribute::Inline(vec![Token::Text(String::from("b"))]),
       BlockLevelAttribute::Table(vec![
         vec![
@@ -161,8 +202,7 @@ mod tests {
 
   #[test]
   fn test_table_multi() {
-    println!("debug: {:?}", tokenizer::tokenize(String::from("b\n||~ a ||~ b ||~ c ||  \n||< d ||> e||=f ||\ng")));
-    assert_eq!(parse_table(&mut tokenizer::tokenize(String::from("b\n||~ a ||~ b ||~ c ||  \n||< d ||> e||=f ||\ng"))), vec![
+    assert_eq!(parse_table(&mut tokenizer::tokenize(String::from("b\n||~ a ||~ b ||~ c ||  \n||< d ||> e||=f ||\ng"), &mut vec![])), vec![
       BlockLevelAttribute::Inline(vec![Token::Text(String::from("b"))]),
       BlockLevelAttribute::Table(vec![
         vec![
@@ -0,0 +1,80 @@
+/// Deepest nesting of block-level frames, or of inline frames inside one paragraph, that the parser accepts.
+/// Frames are not visible levels: each list level takes two (the list and its item), so a 65-deep list already fails.
+/// Deeper trees would overflow the stack when they are rendered or dropped.
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+pub use crate::span::Position;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+  /// The element opened at `position` is nested deeper than `limit` frames.
+  NestingTooDeep{limit: usize, position: Position},
+}
+
+impl std::fmt::Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ParseError::NestingTooDeep { limit, position } => write!(f, "{}: elements are nested deeper than {} parser frames", position, limit),
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+  /// `TreeElement::HtmlElement` has a tag other than `div` or `span`.
+  InvalidTagName{tag: String},
+  /// Elements are nested deeper than `limit`.
+  NestingTooDeep{limit: usize},
+  /// The output format has no markup for `element`, such as `Iframe` in Wikidot markup.
+  UnsupportedElement{element: &'static str},
+}
+
+impl std::fmt::Display for RenderError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RenderError::InvalidTagName { tag } => write!(f, "`{}` is not an allowed tag", tag),
+      RenderError::NestingTooDeep { limit } => write!(f, "elements are nested deeper than {} levels", limit),
+      RenderError::UnsupportedElement { element } => write!(f, "`{}` cannot be written in this format", element),
+    }
+  }
+}
+
+impl std::error::Error for RenderError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+  Parse(ParseError),
+  Render(RenderError),
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Error::Parse(e) => write!(f, "parse error: {}", e),
+      Error::Render(e) => write!(f, "render error: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::Parse(e) => Some(e),
+      Error::Render(e) => Some(e),
+    }
+  }
+}
+
+impl From<ParseError> for Error {
+  fn from(value: ParseError) -> Self {
+    Error::Parse(value)
+  }
+}
+
+impl From<RenderError> for Error {
+  fn from(value: RenderError) -> Self {
+    Error::Render(value)
+  }
+}
@@ -1,49 +1,174 @@
-use crate::{ast::{ParseFrame, TreeElement}, block::BlockLevelAttribute, inline::data_builder::DataBuilder};
+use crate::{ast::{ParseFrame, TreeElement}, block::BlockLevelAttribute, inline::data_builder::DataBuilder, inline::parse_inline::InlineState};
+use crate::error::ParseError;
+use crate::lint::{Warning, WarningKind};
+use crate::span::{Span, SpanTree};
 
 mod data_builder;
 mod parse_inline;
 
-pub fn parse(block_tree: Vec<crate::block::BlockLevelAttribute>) -> Vec<crate::ast::TreeElement> {
+/// `spans` is the tree of token spans that `block::parse` returned alongside `tokens`.
+fn parse_tokens(tokens: Vec<crate::tokenizer::Token>, spans: SpanTree, state: &mut InlineState) -> Result<(Vec<TreeElement>, Vec<SpanTree>), ParseError> {
+  let tokens = tokens.into_iter().zip(spans.children.into_iter().map(|v| v.span).chain(std::iter::repeat(spans.span))).collect();
+  parse_inline::parse_inline(tokens, state)
+}
+
+/// Returns the elements and, in parallel, their `SpanTree`s.
+pub fn parse(block_tree: Vec<crate::block::BlockLevelAttribute>, spans: Vec<SpanTree>, warnings: &mut Vec<Warning>) -> Result<(Vec<crate::ast::TreeElement>, Vec<SpanTree>), ParseError> {
   let mut db = DataBuilder::new();
 
-  let mut iters = vec![block_tree.into_iter()];
+  let end = spans.last().map(|v| v.span.end).unwrap_or_default();
+  let mut iters = vec![(block_tree.into_iter(), spans.into_iter())];
+  let mut toc_count = 0;
+  let mut state = InlineState::default();
+  let mut has_footnote_block = false;
+
+  while let Some((iter, span_iter)) = iters.last_mut() {
+    if let Some(block) = iter.next() {
+      let span_tree = span_iter.next().unwrap_or_default();
+      let span = span_tree.span;
 
-  while !iters.is_empty() {
-    if let Some(block) = iters.last_mut().unwrap().next() {
       match block {
         BlockLevelAttribute::BlockQuote(children) => {
-          db.push(ParseFrame::QuoteBlock);
-          iters.push(children.into_iter());
+          db.push(ParseFrame::QuoteBlock, span);
+          iters.push((children.into_iter(), span_tree.children.into_iter()));
         }
 
         BlockLevelAttribute::TabView(children) => {
-          db.push(ParseFrame::TabView);
-          iters.push(children.into_iter());
+          db.push(ParseFrame::TabView, span);
+          iters.push((children.into_iter(), span_tree.children.into_iter()));
         }
 
         BlockLevelAttribute::Table(table) => {
-          let mut res = vec![vec![]];
+          let mut res = vec![];
+          let mut cell_spans = span_tree.children.into_iter();
+          let mut span_children = vec![]; // セルの中身を行ごとに並べる
+          let mut column_count = None; // 1行目の列数
 
           for vc in table {
+            let mut row = vec![];
+            let mut row_span: Option<Span> = None;
+            let columns = vc.iter().map(|v| v.spanning.get()).sum();
             for item in vc {
-              res.last_mut().unwrap().push(crate::ast::table_cell::Cell {
-                val: parse_inline::parse_inline(item.val),
+              let cell_span = cell_spans.next().unwrap_or_default();
+              row_span = Some(row_span.map_or(cell_span.span, |v| v.merge(cell_span.span)));
+              let (val, spans) = parse_tokens(item.val, cell_span, &mut state)?;
+              span_children.extend(spans);
+              row.push(crate::ast::table_cell::Cell {
+                val,
                 style: item.style,
                 spanning: item.spanning,
               })
             }
+            match column_count {
+              None => column_count = Some(columns),
+              Some(expected) if expected != columns => {
+                state.warnings.push(Warning { kind: WarningKind::InconsistentColumns { expected, found: columns }, span: row_span.unwrap_or(span) });
+              }
+              Some(_) => {}
+            }
+            res.push(row);
+          }
+
+          db.add(TreeElement::Table(res), SpanTree { span, children: span_children });
+        }
+
+        BlockLevelAttribute::DefinitionList(items) => {
+          let mut res = vec![];
+          let mut item_spans = span_tree.children.into_iter();
+          let mut span_children = vec![]; // 用語と説明の中身を項目ごとに並べる
+
+          for (term, definition) in items {
+            let mut spans = item_spans.next().unwrap_or_default().children.into_iter();
+            let (term, term_spans) = parse_tokens(term, spans.next().unwrap_or_default(), &mut state)?;
+            let (definition, definition_spans) = parse_tokens(definition, spans.next().unwrap_or_default(), &mut state)?;
+            span_children.extend(term_spans);
+            span_children.extend(definition_spans);
+            res.push((term, definition));
           }
 
-          db.add(TreeElement::Table(res));
+          db.add(TreeElement::DefinitionList(res), SpanTree { span, children: span_children });
         }
 
         BlockLevelAttribute::Tab { title, children } => {
-          db.push(ParseFrame::Tab(title));
-          iters.push(children.into_iter());
+          db.push(ParseFrame::Tab(title), span);
+          iters.push((children.into_iter(), span_tree.children.into_iter()));
+        }
+
+        BlockLevelAttribute::Heading { level, in_toc, children } => {
+          let anchor = if in_toc {
+            toc_count += 1;
+            Some(format!("toc{}", toc_count - 1))
+          } else {
+            None
+          };
+
+          let (children, span_children) = parse_tokens(children, span_tree, &mut state)?;
+          db.add(TreeElement::Heading { level, children, anchor }, SpanTree { span, children: span_children });
+        }
+
+        BlockLevelAttribute::List { kind, children } => {
+          db.push(ParseFrame::List { kind }, span);
+          iters.push((children.into_iter(), span_tree.children.into_iter()));
+        }
+
+        BlockLevelAttribute::ListItem(children) => {
+          db.push(ParseFrame::ListItem, span);
+          iters.push((children.into_iter(), span_tree.children.into_iter()));
+        }
+
+        BlockLevelAttribute::Div { property, children } => {
+          db.push(ParseFrame::HtmlElement { tag: String::from("div"), property }, span);
+          iters.push((children.into_iter(), span_tree.children.into_iter()));
+        }
+
+        BlockLevelAttribute::Collapsible { show, hide, folded, hide_location, children } => {
+          db.push(ParseFrame::Collapsible { show, hide, folded, hide_location }, span);
+          iters.push((children.into_iter(), span_tree.children.into_iter()));
+        }
+
+        BlockLevelAttribute::Align { alignment, children } => {
+          db.push(ParseFrame::Align(alignment), span);
+          iters.push((children.into_iter(), span_tree.children.into_iter()));
+        }
+
+        BlockLevelAttribute::FootnoteBlock { title } => {
+          has_footnote_block = true;
+          db.add(TreeElement::FootnoteBlock { title }, span_tree);
+        }
+
+        BlockLevelAttribute::TableOfContents { float } => {
+          db.add(TreeElement::TableOfContents { float }, span_tree);
+        }
+
+        BlockLevelAttribute::HorizontalRule => {
+          db.add(TreeElement::HorizontalRule, span_tree);
+        }
+
+        BlockLevelAttribute::ClearFloat(float) => {
+          db.add(TreeElement::ClearFloat(float), span_tree);
+        }
+
+        BlockLevelAttribute::CodeBlock { language, source } => {
+          db.add(TreeElement::CodeBlock { language, source }, span_tree);
         }
 
         BlockLevelAttribute::Inline(children) => {
-          db.add(TreeElement::Paragraph(parse_inline::parse_inline(children)));
+          let (mut children, mut span_children) = parse_tokens(children, span_tree, &mut state)?;
+          // 閉じタグだけの行を読み飛ばすと段落の端に改行が残るので, 取り除く
+          let leading = children.iter().take_while(|v| **v == TreeElement::NewLine).count();
+          children.drain(..leading);
+          span_children.drain(..leading);
+          let trailing = children.iter().rev().take_while(|v| **v == TreeElement::NewLine).count();
+          children.truncate(children.len() - trailing);
+          span_children.truncate(span_children.len() - trailing);
+
+          if let Some(ParseFrame::ListItem) = db.last_frame_mut() { // リスト項目の中身は段落にしない
+            for (v, span_tree) in children.into_iter().zip(span_children) {
+              db.add(v, span_tree);
+            }
+          } else if !children.is_empty() { // 中身がすべて読み飛ばされた段落は作らない
+            db.add(TreeElement::Paragraph(children), SpanTree { span, children: span_children });
+          }
         }
       }
     } else {
@@ -52,6 +177,143 @@ pub fn parse(block_tree: Vec<crate::block::BlockLevelAttribute>) -> Vec<crate::a
     }
   }
 
-  db.into()
+  if !has_footnote_block && state.footnote_count > 0 {
+    db.add(TreeElement::FootnoteBlock { title: None }, SpanTree::leaf(Span::empty(end)));
+  }
+
+  warnings.append(&mut state.warnings);
+  Ok(db.into())
 }
 
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ast::table_cell::{Cell, Style};
+
+  fn nz(v: usize) -> std::num::NonZeroUsize {
+    std::num::NonZeroUsize::try_from(v).unwrap()
+  }
+
+  fn parse_str(s: &str) -> Vec<TreeElement> {
+    parse_with_spans(s).0
+  }
+
+  fn parse_with_spans(s: &str) -> (Vec<TreeElement>, Vec<SpanTree>) {
+    let (block_tree, spans) = crate::block::parse(crate::tokenizer::tokenize(String::from(s), &mut vec![]), &mut vec![]).unwrap();
+    parse(block_tree, spans, &mut vec![]).unwrap()
+  }
+
+  #[test]
+  fn test_spans() {
+    // (開始, 終了, 子の数) を行きがけ順に並べる
+    fn offsets(spans: &[SpanTree], res: &mut Vec<(usize, usize, usize)>) {
+      for v in spans {
+        res.push((v.span.start.offset, v.span.end.offset, v.children.len()));
+        offsets(&v.children, res);
+      }
+    }
+
+    let (ast, spans) = parse_with_spans("para **bold**\n> quote");
+    let mut res = vec![];
+    offsets(&spans, &mut res);
+
+    assert_eq!(ast.len(), 2);
+    assert_eq!(res, vec![
+      (0, 13, 2), // Paragraph
+      (0, 5, 0), // "para "
+      (5, 13, 1), // Bold
+      (7, 11, 0), // "bold"
+      (14, 21, 1), // QuoteBlock
+      (16, 21, 1), // Paragraph
+      (16, 21, 0), // "quote"
+    ]);
+    assert_eq!((spans[1].span.start.line, spans[1].span.start.column), (2, 1));
+  }
+
+  #[test]
+  fn test_table_rows() {
+    let cell = |s: &str, style: Option<Style>, spanning: usize| Cell { val: vec![TreeElement::Text(String::from(s))], style, spanning: nz(spanning) };
+
+    assert_eq!(parse_str("||~ a ||~ b ||\n|| c || d ||\n|||| e ||"), vec![
+      TreeElement::Table(vec![
+        vec![cell(" a ", Some(Style::Title), 1), cell(" b ", Some(Style::Title), 1)],
+        vec![cell(" c ", None, 1), cell(" d ", None, 1)],
+        vec![cell(" e ", None, 2)],
+      ]),
+    ]);
+  }
+
+  #[test]
+  fn test_table_of_contents() {
+    use crate::ast::Float;
+
+    assert_eq!(parse_str("[[toc]]\n[[f<toc]]\n[[f>toc]]\n+ a"), vec![
+      TreeElement::TableOfContents { float: None },
+      TreeElement::TableOfContents { float: Some(Float::Left) },
+      TreeElement::TableOfContents { float: Some(Float::Right) },
+      TreeElement::Heading { level: 1, children: vec![TreeElement::Text(String::from("a"))], anchor: Some(String::from("toc0")) },
+    ]);
+  }
+
+  #[test]
+  fn test_definition_list() {
+    let text = |s: &str| TreeElement::Text(String::from(s));
+    assert_eq!(parse_str("a\n: **b** : c\n: d : e[[footnote]]f[[/footnote]]\n\n: g : h\ni"), vec![
+      TreeElement::Paragraph(vec![text("a")]),
+      TreeElement::DefinitionList(vec![
+        (vec![TreeElement::Bold(vec![text("b")])], vec![text("c")]),
+        (vec![text("d")], vec![text("e"), TreeElement::Footnote { id: 1, children: vec![text("f")] }]),
+      ]),
+      TreeElement::DefinitionList(vec![(vec![text("g")], vec![text("h")])]),
+      TreeElement::Paragraph(vec![text("i")]),
+      TreeElement::FootnoteBlock { title: None },
+    ]);
+
+    let (ast, spans) = parse_with_spans(": ab : c\n: d : e");
+    assert_eq!(ast[0].children().len(), spans[0].children.len());
+    assert_eq!((spans[0].span.start.offset, spans[0].span.end.offset), (0, 16));
+    assert_eq!((spans[0].children[0].span.start.offset, spans[0].children[1].span.start.offset), (2, 7));
+  }
+
+  #[test]
+  fn test_align() {
+    use crate::ast::TextAlign;
+
+    let text = |s: &str| TreeElement::Paragraph(vec![TreeElement::Text(String::from(s))]);
+    assert_eq!(parse_str("[[=]]\na\n[[==]]\n> b\n[[/==]]\n[[/=]]\n[[<]]\nc\n[[/<]]\n[[>]]d[[/>]]"), vec![
+      TreeElement::Align { alignment: TextAlign::Center, children: vec![
+        text("a"),
+        TreeElement::Align { alignment: TextAlign::Justify, children: vec![TreeElement::QuoteBlock(vec![text("b")])] },
+      ] },
+      TreeElement::Align { alignment: TextAlign::Left, children: vec![text("c")] },
+      TreeElement::Align { alignment: TextAlign::Right, children: vec![text("d")] },
+    ]);
+  }
+
+  #[test]
+  fn test_separators() {
+    use crate::ast::Float;
+
+    assert_eq!(parse_str("a\n----\nb\n~~~~>\n* c\n[[clearfloat]]"), vec![
+      TreeElement::Paragraph(vec![TreeElement::Text(String::from("a"))]),
+      TreeElement::HorizontalRule,
+      TreeElement::Paragraph(vec![TreeElement::Text(String::from("b"))]),
+      TreeElement::ClearFloat(Some(Float::Right)),
+      TreeElement::List { kind: crate::ast::ListKind::Unordered, children: vec![TreeElement::ListItem(vec![TreeElement::Text(String::from("c"))])] },
+      TreeElement::ClearFloat(None),
+    ]);
+  }
+  #[test]
+  fn test_skipped_markup() {
+    use crate::ast::TextAlign;
+
+    // 読み飛ばしたマークアップの跡を残さない
+    let text = |s: &str| TreeElement::Paragraph(vec![TreeElement::Text(String::from(s))]);
+    assert_eq!(parse_str("##[[=]]\na\n[[/=]]"), vec![TreeElement::Align { alignment: TextAlign::Center, children: vec![text("a")] }]);
+    assert_eq!(parse_str("[[/tab]]* [[code]]# "), vec![text("* # ")]);
+    assert_eq!(parse_str("[[/span]]\na\n[[/span]]\nb\n[[/span]]"), vec![TreeElement::Paragraph(vec![
+      TreeElement::Text(String::from("a")), TreeElement::NewLine, TreeElement::Text(String::from("b")),
+    ])]);
+  }
+}
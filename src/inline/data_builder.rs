@@ -1,8 +1,11 @@
 use crate::ast::{ParseFrame, TreeElement};
+use crate::span::{Span, SpanTree};
 
 pub struct DataBuilder {
   root: Vec<TreeElement>,
   data: Vec<(ParseFrame, Vec<TreeElement>)>,
+  root_spans: Vec<SpanTree>,
+  spans: Vec<(Span, Vec<SpanTree>)>, // dataと同じ深さで, ブロックの範囲と子のSpanTreeを持つ
 }
 
 impl DataBuilder {
@@ -10,36 +13,46 @@ impl DataBuilder {
     Self {
       root: vec![],
       data: vec![],
+      root_spans: vec![],
+      spans: vec![],
     }
   }
 
   pub fn pop_and_merge(&mut self) -> bool {
     if let Some((frame, elem)) = self.data.pop() {
-      let push_target: &mut Vec<TreeElement>;
-      if self.data.is_empty() {
-        push_target = &mut self.root;
-      } else {
-        push_target = &mut self.data.last_mut().unwrap().1;
+      let (span, span_children) = self.spans.pop().unwrap_or_default();
+      match frame.into_tree_element(elem) {
+        Ok(element) => self.add(element, SpanTree { span, children: span_children }),
+        Err(children) => { // 枠だけを捨てて中身を残す
+          for (element, span_tree) in children.into_iter().zip(span_children) {
+            self.add(element, span_tree);
+          }
+        }
       }
 
-      push_target.push(frame.into_tree_element(elem));
-
       true
     } else {
       false
     }
   }
 
-  pub fn push(&mut self, fr: ParseFrame) {
+  pub fn push(&mut self, fr: ParseFrame, span: Span) {
     self.data.push((fr, vec![]));
+    self.spans.push((span, vec![]));
   }
 
-  pub fn add(&mut self, item: TreeElement) {
+  pub fn add(&mut self, item: TreeElement, span_tree: SpanTree) {
     if let Some((_, elem)) = self.data.last_mut() {
       elem.push(item);
     } else {
       self.root.push(item);
     }
+
+    if let Some((_, spans)) = self.spans.last_mut() {
+      spans.push(span_tree);
+    } else {
+      self.root_spans.push(span_tree);
+    }
   }
 
   pub fn last_frame_mut(&mut self) -> Option<&mut ParseFrame> {
@@ -51,9 +64,9 @@ impl DataBuilder {
   }
 }
 
-impl From<DataBuilder> for Vec<TreeElement> {
-  fn from(mut data: DataBuilder) -> Vec<TreeElement> {
+impl From<DataBuilder> for (Vec<TreeElement>, Vec<SpanTree>) {
+  fn from(mut data: DataBuilder) -> (Vec<TreeElement>, Vec<SpanTree>) {
     while data.pop_and_merge() {}
-    data.root
+    (data.root, data.root_spans)
   }
-}
\ No newline at end of file
+}
@@ -2,86 +2,169 @@ mod inline_builder;
 
 use crate::ast;
 use crate::tokenizer::Token;
+use crate::error::ParseError;
+use crate::lint::{Warning, WarningKind};
+use crate::span::{Span, SpanTree};
+
+/// State shared by every `parse_inline` call on the same page.
+#[derive(Debug, Default)]
+pub struct InlineState {
+  pub footnote_count: u32,
+  pub warnings: Vec<Warning>,
+}
+
+impl InlineState {
+  fn warn(&mut self, kind: WarningKind, span: Span) {
+    self.warnings.push(Warning { kind, span });
+  }
+}
 
-pub fn parse_inline(tokens: Vec<crate::tokenizer::Token>) -> Vec<crate::ast::TreeElement> {
+/// Reads the attributes of `[[image]]`. Sizes are pixels, with or without `px`; anything else is ignored.
+fn image_element(alignment: Option<ast::Alignment>, float: bool, source: String, attributes: Vec<(String, String)>) -> ast::TreeElement {
+  let get = |key: &str| attributes.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.clone());
+  let pixels = |key: &str| get(key).and_then(|v| v.trim().trim_end_matches("px").parse::<u32>().ok());
+
+  ast::TreeElement::Image {
+    source,
+    alt: get("alt"),
+    title: get("title"),
+    width: pixels("width"),
+    height: pixels("height"),
+    link: get("link"),
+    alignment,
+    float,
+    class: get("class"),
+    style: get("style"),
+  }
+}
+
+/// Returns the elements and, in parallel, their `SpanTree`s.
+pub fn parse_inline(tokens: Vec<(crate::tokenizer::Token, Span)>, state: &mut InlineState) -> Result<(Vec<crate::ast::TreeElement>, Vec<SpanTree>), ParseError> {
   let mut db = inline_builder::InlineBuilder::new();
 
-  for token in tokens {
+  for (token, span) in tokens {
+    db.set_span(span);
     if let Ok(frame) = token.clone().try_into() {
-      db.switch_element(frame);
+      db.switch_element(frame)?;
       continue;
     } else {
       match token {
-        Token::Bold | Token::Italics | Token::Underline | Token::Strikethrough | Token::Superscript | Token::Subscript => {
-          unreachable!() // handled above
-        }
+        Token::Bold | Token::Italics | Token::Underline | Token::Strikethrough | Token::Superscript | Token::Subscript => {} // handled above
 
         Token::MonospacedOpen => {
-          db.push(ast::ParseFrame::Monospaced);
+          db.push(ast::ParseFrame::Monospaced)?;
         }
 
         Token::MonospacedClose => {
-          db.close_element(ast::ParseFrameKind::Monospaced);
+          if !db.close_element(ast::ParseFrameKind::Monospaced) {
+            state.warn(WarningKind::UnmatchedClose { markup: String::from("}}") }, span);
+          }
         }
 
         Token::ElementBegin { name, attributes } => {
-          todo!();
+          match name.as_str() {
+            "span" => {
+              db.push(ast::ParseFrame::HtmlElement { tag: name, property: attributes })?;
+            }
+
+            "size" => {
+              // 値のない語を並べ直す. 属性の形で書かれたものは=が残るので通らない
+              let value = attributes.iter().map(|(k, v)| if k.is_empty() { v.clone() } else { format!("{}={}", k, v) }).collect::<Vec<_>>().join(" ");
+              // 不正な値でも枠は開き, [[/size]]と対応させる
+              let scale = ast::CssSize::new(&value);
+              if scale.is_none() {
+                state.warn(WarningKind::InvalidSize { value }, span);
+              }
+              db.push(ast::ParseFrame::Size { scale })?;
+            }
+
+            "footnote" => {
+              state.footnote_count += 1;
+              db.push(ast::ParseFrame::Footnote { id: state.footnote_count })?;
+            }
+
+            "code" => { // [[/code]]がない
+              state.warn(WarningKind::Unclosed { markup: String::from("[[code]]") }, span);
+            }
+
+            &_ => { // unknown elements are dropped
+              state.warn(WarningKind::UnknownElement { name }, span);
+            }
+          }
         }
 
         Token::ElementEnd(name) => {
-          todo!();
+          let is_closed = match name.as_str() {
+            "span" => db.close_element(ast::ParseFrameKind::HtmlElement { tag: name.clone() }),
+            "footnote" => db.close_element(ast::ParseFrameKind::Footnote),
+            "size" => db.close_element(ast::ParseFrameKind::Size),
+            &_ => false,
+          };
+          if !is_closed {
+            state.warn(WarningKind::UnmatchedClose { markup: format!("[[/{}]]", name) }, span);
+          }
+        }
+
+        Token::Code { language, source } => { // 見出しや表の中に置かれた場合
+          db.add(ast::TreeElement::CodeBlock { language, source });
         }
 
         Token::ColoredBeginColorName(name) => {
-          db.push(match name.as_str() {
-            "aqua"    => ast::ParseFrame::Colored { red: 0x00, green: 0xFF, blue: 0xFF },
-            "black"   => ast::ParseFrame::Colored { red: 0x00, green: 0x00, blue: 0x00 },
-            "blue"    => ast::ParseFrame::Colored { red: 0x00, green: 0x00, blue: 0xFF },
-            "fuchsia" => ast::ParseFrame::Colored { red: 0xFF, green: 0x00, blue: 0xFF }, // magenta
-            "grey"    => ast::ParseFrame::Colored { red: 0x80, green: 0x80, blue: 0x80 },
-            "green"   => ast::ParseFrame::Colored { red: 0x00, green: 0x80, blue: 0x00 },
-            "lime"    => ast::ParseFrame::Colored { red: 0x00, green: 0xFF, blue: 0x00 },
-            "maroon"  => ast::ParseFrame::Colored { red: 0x80, green: 0x00, blue: 0x00 },
-            "navy"    => ast::ParseFrame::Colored { red: 0x00, green: 0x00, blue: 0x80 },
-            "olive"   => ast::ParseFrame::Colored { red: 0x80, green: 0x80, blue: 0x00 },
-            "purple"  => ast::ParseFrame::Colored { red: 0x80, green: 0x00, blue: 0x80 },
-            "red"     => ast::ParseFrame::Colored { red: 0xFF, green: 0x00, blue: 0x00 },
-            "silver"  => ast::ParseFrame::Colored { red: 0xC0, green: 0xC0, blue: 0xC0 },
-            "teal"    => ast::ParseFrame::Colored { red: 0x00, green: 0x80, blue: 0x80 },
-            "white"   => ast::ParseFrame::Colored { red: 0xFF, green: 0xFF, blue: 0xFF },
-            "yellow"  => ast::ParseFrame::Colored { red: 0xFF, green: 0xFF, blue: 0x00 },
-            &_        => unreachable!(),
-          });
+          let (red, green, blue) = ast::WikidotColor::from(&name).map_or((0x00, 0x00, 0x00), ast::WikidotColor::rgb);
+          db.push(ast::ParseFrame::Colored { red, green, blue })?;
         }
 
         Token::ColoredBeginColorCode(code) => {
-          let chars: Vec<char> = code.chars().collect();
+          let channel = |range: std::ops::Range<usize>| code.get(range).and_then(|v| u8::from_str_radix(v, 16).ok()).unwrap_or(0);
 
-          let r = u8::from_str_radix(&chars[0..2].iter().collect::<String>(), 16).unwrap_or(0);
-          let g = u8::from_str_radix(&chars[2..4].iter().collect::<String>(), 16).unwrap_or(0);
-          let b = u8::from_str_radix(&chars[4..6].iter().collect::<String>(), 16).unwrap_or(0);
+          let r = channel(0..2);
+          let g = channel(2..4);
+          let b = channel(4..6);
 
-          db.push(ast::ParseFrame::Colored { red: r, green: g, blue: b });
+          db.push(ast::ParseFrame::Colored { red: r, green: g, blue: b })?;
         }
 
         Token::ColoredEnd => {
-          db.close_element(ast::ParseFrameKind::Colored);
+          if !db.close_element(ast::ParseFrameKind::Colored) {
+            state.warn(WarningKind::UnmatchedClose { markup: String::from("##") }, span);
+          }
         }
 
         Token::NamedLink { link, name } => {
+          if link.is_empty() {
+            state.warn(WarningKind::EmptyLink, span);
+          }
           db.add(ast::TreeElement::Link { href: ast::Url(link), open_in_new_tab: false, name });
         }
 
         Token::PageLink { link, name } => {
-          db.add(ast::TreeElement::InternalLink { href: link, open_in_new_tab: false, name });
+          let link = link.trim();
+          let (href, open_in_new_tab) = match link.strip_prefix('*') { // [[[*page]]]は新しいタブで開く
+            Some(v) => (String::from(v), true),
+            None => (String::from(link), false),
+          };
+          if href.trim().is_empty() {
+            state.warn(WarningKind::EmptyLink, span);
+          }
+          db.add(ast::TreeElement::InternalLink { href, open_in_new_tab, name: String::from(name.trim()) });
         }
 
-        Token::BlockQuote(level) => {
-          unreachable!(); // already handled in block parsing
+        Token::Image { alignment, float, source, attributes } => {
+          db.add(image_element(alignment, float, source, attributes));
         }
 
-        Token::CellSeparator(_) => {
-          unreachable!(); // already handled in block parsing
+        Token::BlockQuote(_) | Token::Heading { .. } | Token::ListItem { .. } | Token::HorizontalRule | Token::ClearFloat(_)
+          | Token::DefinitionTerm | Token::DefinitionSeparator => {} // already handled in block parsing
+
+        Token::CellSeparator(style) => { // 表の外に残った区切りはそのまま出す
+          let marker = match style {
+            None => "||",
+            Some(ast::table_cell::Style::Title) => "||~",
+            Some(ast::table_cell::Style::LeftAligned) => "||<",
+            Some(ast::table_cell::Style::RightAligned) => "||>",
+            Some(ast::table_cell::Style::CenterAligned) => "||=",
+          };
+          db.add(ast::TreeElement::Text(String::from(marker)));
         }
 
         Token::NewLine => {
@@ -95,7 +178,7 @@ pub fn parse_inline(tokens: Vec<crate::tokenizer::Token>) -> Vec<crate::ast::Tre
     }
   }
 
-  db.into()
+  Ok(db.finish(&mut state.warnings))
 }
 
 #[cfg(test)]
@@ -103,6 +186,11 @@ mod tests {
   use super::*;
   use crate::ast::TreeElement;
 
+  // inline.rsと同じく, 行内要素の列を段落に包んで返す
+  fn parse_inline(tokens: Vec<Token>) -> Vec<TreeElement> {
+    vec![TreeElement::Paragraph(super::parse_inline(tokens.into_iter().map(|v| (v, Span::default())).collect(), &mut InlineState::default()).unwrap().0)]
+  }
+
   fn make_paragraph(children: Vec<TreeElement>) -> Vec<TreeElement> {
     vec![TreeElement::Paragraph(children)]
   }
@@ -127,11 +215,8 @@ mod tests {
       Token::Text("world".to_string()),
     ];
     let result = parse_inline(tokens);
-    let expected = make_paragraph(vec![
-      text("Hello"),
-      text(" "),
-      text("world"),
-    ]);
+    // 並んだ文字列は1つにまとめる
+    let expected = make_paragraph(vec![text("Hello world")]);
     assert_eq!(result, expected);
   }
 
@@ -408,6 +493,25 @@ mod tests {
     assert_eq!(result, expected);
   }
 
+  #[test]
+  fn test_page_link_new_tab() {
+    let tokens = vec![
+      Token::PageLink {
+        link: "*scp-173 ".to_string(),
+        name: " Sculpture ".to_string(),
+      },
+    ];
+    let result = parse_inline(tokens);
+    let expected = make_paragraph(vec![
+      TreeElement::InternalLink {
+        href: "scp-173".to_string(),
+        open_in_new_tab: true,
+        name: "Sculpture".to_string(),
+      },
+    ]);
+    assert_eq!(result, expected);
+  }
+
   #[test]
   fn test_newline() {
     let tokens = vec![
@@ -433,10 +537,10 @@ mod tests {
       Token::Text("b".to_string()),
     ];
     let result = parse_inline(tokens);
+    // 続く改行は1つにまとめる
     let expected = make_paragraph(vec![
       text("a"),
       TreeElement::NewLine,
-      TreeElement::NewLine,
       text("b"),
     ]);
     assert_eq!(result, expected);
@@ -633,6 +737,89 @@ mod tests {
     assert_eq!(result, expected);
   }
 
+  #[test]
+  fn test_span() {
+    let tokens = vec![
+      Token::Text("a".to_string()),
+      Token::ElementBegin { name: "span".to_string(), attributes: vec![("class".to_string(), "x".to_string())] },
+      Token::Bold,
+      Token::Text("b".to_string()),
+      Token::ElementEnd("span".to_string()),
+      Token::Text("c".to_string()),
+    ];
+    let result = parse_inline(tokens);
+    let expected = make_paragraph(vec![
+      text("a"),
+      TreeElement::HtmlElement {
+        tag: "span".to_string(),
+        property: vec![("class".to_string(), "x".to_string())],
+        children: vec![TreeElement::Bold(vec![text("b")])],
+      },
+      TreeElement::Bold(vec![text("c")]),
+    ]);
+    assert_eq!(result, expected);
+  }
+
+  #[test]
+  fn test_stray_span_end() {
+    let tokens = vec![
+      Token::Text("a".to_string()),
+      Token::ElementEnd("span".to_string()),
+      Token::ElementBegin { name: "unknown".to_string(), attributes: vec![] },
+      Token::Text("b".to_string()),
+    ];
+    let result = parse_inline(tokens);
+    let expected = make_paragraph(vec![text("ab")]);
+    assert_eq!(result, expected);
+  }
+
+  #[test]
+  fn test_stray_close_in_footnote() {
+    // 脚注の外で開いたものは脚注の中では閉じない. 脚注が2つに分かれて同じidになってしまう
+    let tokens = vec![
+      Token::Bold,
+      Token::ElementBegin { name: "footnote".to_string(), attributes: vec![] },
+      Token::Text("a".to_string()),
+      Token::ElementEnd("span".to_string()),
+      Token::Bold,
+      Token::Text("b".to_string()),
+      Token::Bold,
+      Token::ElementEnd("footnote".to_string()),
+      Token::Text("c".to_string()),
+      Token::Bold,
+    ];
+    let result = parse_inline(tokens);
+    let expected = make_paragraph(vec![
+      TreeElement::Bold(vec![
+        TreeElement::Footnote { id: 1, children: vec![text("a"), TreeElement::Bold(vec![text("b")])] },
+        text("c"),
+      ]),
+    ]);
+    assert_eq!(result, expected);
+  }
+
+  #[test]
+  fn test_footnote_numbering() {
+    let mut state = InlineState { footnote_count: 2, ..Default::default() };
+    let tokens = vec![
+      Token::Text("a".to_string()),
+      Token::ElementBegin { name: "footnote".to_string(), attributes: vec![] },
+      Token::Text("b".to_string()),
+      Token::ElementEnd("footnote".to_string()),
+      Token::ElementBegin { name: "footnote".to_string(), attributes: vec![] },
+      Token::Text("c".to_string()),
+      Token::ElementEnd("footnote".to_string()),
+    ];
+    let result = vec![TreeElement::Paragraph(super::parse_inline(tokens.into_iter().map(|v| (v, Span::default())).collect(), &mut state).unwrap().0)];
+    let expected = make_paragraph(vec![
+      text("a"),
+      TreeElement::Footnote { id: 3, children: vec![text("b")] },
+      TreeElement::Footnote { id: 4, children: vec![text("c")] },
+    ]);
+    assert_eq!(result, expected);
+    assert_eq!(state.footnote_count, 4);
+  }
+
   // Tests for wrongly-layered code (allowed but still tested)
   #[test]
   fn test_unclosed_bold() {
@@ -1,8 +1,21 @@
 use crate::ast;
+use crate::error::{ParseError, MAX_NESTING_DEPTH};
+use crate::lint::{Warning, WarningKind};
+use crate::span::{Position, Span, SpanTree};
+
+#[derive(Default)]
+struct FrameSpans {
+  opener: Span, // 開いたトークン. 閉じ忘れの警告に使う
+  start: Position, // 開き直した要素はopenerより後から始まる
+  children: Vec<SpanTree>,
+}
 
 pub struct InlineBuilder {
   root: Vec<ast::TreeElement>,
   data: Vec<(ast::ParseFrame, Vec<ast::TreeElement>)>,
+  root_spans: Vec<SpanTree>,
+  spans: Vec<FrameSpans>, // dataと同じ深さ
+  span: Span, // 今読んでいるトークン
 }
 
 impl InlineBuilder {
@@ -10,70 +23,132 @@ impl InlineBuilder {
     Self {
       root: vec![],
       data: vec![],
+      root_spans: vec![],
+      spans: vec![],
+      span: Span::default(),
+    }
+  }
+
+  /// Called for every token before it is handled. Frames closed while handling it end after it.
+  pub fn set_span(&mut self, span: Span) {
+    self.span = span;
+  }
+
+  /// Adds `element` to the innermost frame. Text right after text is appended to it, and a line break right after
+  /// a line break is dropped.
+  fn add_with_span(&mut self, element: ast::TreeElement, span_tree: SpanTree) {
+    let (container, span_children) = match (self.data.last_mut(), self.spans.last_mut()) {
+      (Some((_, container)), Some(frame_spans)) => (container, &mut frame_spans.children),
+      _ => (&mut self.root, &mut self.root_spans),
+    };
+
+    // 間のトークンを読み飛ばすと文字列や改行が並ぶので, 1つにまとめる. 空行は段落を分けるので, 改行は続かない
+    if element == ast::TreeElement::NewLine && container.last() == Some(&ast::TreeElement::NewLine) {
+      return;
+    }
+    if let ast::TreeElement::Text(text) = &element
+      && let Some(ast::TreeElement::Text(last)) = container.last_mut()
+      && let Some(last_span) = span_children.last_mut()
+    {
+      *last += text;
+      last_span.span = last_span.span.merge(span_tree.span);
+      return;
     }
+
+    container.push(element);
+    span_children.push(span_tree);
   }
 
-  pub fn pop_and_merge(&mut self) -> Option<ast::ParseFrame> {
+  /// Returns the closed frame and the span of the token that opened it.
+  fn pop_and_merge(&mut self) -> Option<(ast::ParseFrame, Span)> {
     if let Some((frame, container)) = self.data.pop() {
-      if let Some((_, parent_container)) = self.data.last_mut() {
-        parent_container.push(frame.clone().into_tree_element(container));
-      } else {
-        self.root.push(frame.clone().into_tree_element(container));
+      let FrameSpans { opener, start, children } = self.spans.pop().unwrap_or_default();
+      let span_tree = SpanTree { span: Span { start, end: self.span.end.max(start) }, children };
+      match frame.clone().into_tree_element(container) {
+        Ok(element) => self.add_with_span(element, span_tree),
+        Err(children) => { // 枠だけを捨てて中身を残す
+          for (element, span_tree) in children.into_iter().zip(span_tree.children) {
+            self.add_with_span(element, span_tree);
+          }
+        }
       }
 
-      Some(frame)
+      Some((frame, opener))
     } else {
       None
     }
   }
 
+  /// Whether `close_element(kind)` would reach a frame. A footnote is not closed and reopened by markup
+  /// opened outside it, since a reopened footnote would be a second note with the same id.
+  fn is_open(&self, kind: &ast::ParseFrameKind) -> bool {
+    for (frame, _) in self.data.iter().rev() {
+      let frame_kind = frame.get_kind();
+      if frame_kind == *kind {
+        return true;
+      }
+      if frame_kind == ast::ParseFrameKind::Footnote {
+        return false;
+      }
+    }
+    false
+  }
+
+  /// Returns `false` if no frame of `kind` was open, leaving the open frames as they are.
   pub fn close_element(&mut self, kind: ast::ParseFrameKind) -> bool {
-    let mut frame_to_reopen: Vec<ast::ParseFrame> = vec![]; // Frames that need to be reopened
+    if !self.is_open(&kind) {
+      return false;
+    }
+
+    let mut frame_to_reopen: Vec<(ast::ParseFrame, Span)> = vec![]; // Frames that need to be reopened
 
     let mut reached = false;
-    while let Some(frame) = self.pop_and_merge() {
+    while let Some((frame, opener)) = self.pop_and_merge() {
       if frame.get_kind() == kind {
         reached = true;
         break;
       } else {
-        frame_to_reopen.push(frame);
+        frame_to_reopen.push((frame, opener));
       }
     }
 
-    for frame in frame_to_reopen {
-      self.push(frame);
+    for (frame, opener) in frame_to_reopen { // 閉じた分より深くはならない
+      self.data.push((frame, vec![]));
+      self.spans.push(FrameSpans { opener, start: self.span.end, children: vec![] });
     }
 
     reached
   }
 
-  pub fn switch_element(&mut self, param_frame: ast::ParseFrame) {
-    for (frame, _) in &self.data {
-      if frame.get_kind() == param_frame.get_kind() {
-        self.close_element(param_frame.get_kind()); // if found: open
-        return;
-      }
+  pub fn switch_element(&mut self, param_frame: ast::ParseFrame) -> Result<(), ParseError> {
+    if self.is_open(&param_frame.get_kind()) {
+      self.close_element(param_frame.get_kind()); // if found: close
+      return Ok(());
     }
 
-    self.push(param_frame); // if not found: close
+    self.push(param_frame) // if not found: open
   }
 
-  pub fn push(&mut self, frame: ast::ParseFrame) {
+  pub fn push(&mut self, frame: ast::ParseFrame) -> Result<(), ParseError> {
+    if self.data.len() >= MAX_NESTING_DEPTH {
+      return Err(ParseError::NestingTooDeep { limit: MAX_NESTING_DEPTH, position: self.span.start });
+    }
     self.data.push((frame, vec![]));
+    self.spans.push(FrameSpans { opener: self.span, start: self.span.start, children: vec![] });
+    Ok(())
   }
 
+  /// Adds an element read from the current token.
   pub fn add(&mut self, element: ast::TreeElement) {
-    if let Some((_, container)) = self.data.last_mut() {
-      container.push(element);
-    } else {
-      self.root.push(element);
-    }
+    self.add_with_span(element, SpanTree::leaf(self.span));
   }
-}
 
-impl From<InlineBuilder> for Vec<ast::TreeElement> {
-  fn from(mut builder: InlineBuilder) -> Vec<ast::TreeElement> {
-    while let Some(_) = builder.pop_and_merge() {}
-    builder.root
+  /// Closes the frames left open, warning about each of them.
+  pub fn finish(mut self, warnings: &mut Vec<Warning>) -> (Vec<ast::TreeElement>, Vec<SpanTree>) {
+    while let Some((frame, opener)) = self.pop_and_merge() {
+      let markup = crate::lint::opening_markup(&frame.get_kind());
+      warnings.push(Warning { kind: WarningKind::Unclosed { markup }, span: opener });
+    }
+    (self.root, self.root_spans)
   }
 }
\ No newline at end of file
@@ -1,21 +1,102 @@
 mod tokenizer;
 mod block;
 mod inline;
-mod ast;
-mod renderer;
+pub mod ast;
+pub mod span;
+pub mod error;
+pub mod lint;
+pub mod visit;
+pub mod toc;
+pub mod renderer;
 
-pub fn parse(s: String) -> String {
-  // get LF string
-  let s = s.replace("\r\n", "\n"); // CRLF -> LF
-  let s = s.replace("\r", "\n"); // CR -> LF
+pub fn parse(s: String) -> Result<String, error::Error> {
+  parse_with_options(s, &renderer::xhtml::RenderOptions::default())
+}
+
+pub fn parse_with_options(s: String, options: &renderer::xhtml::RenderOptions) -> Result<String, error::Error> {
+  let ast = parse_to_ast(s)?;
+  //println!("{:#?}", ast);
+  Ok(renderer::xhtml::render_with_options(ast, options)?)
+}
+
+/// Parses the page into its AST without rendering it.
+pub fn parse_to_ast(s: String) -> Result<Vec<ast::TreeElement>, error::ParseError> {
+  Ok(parse_with_spans(s)?.0)
+}
+
+/// Parses the page into its AST and, in parallel, a `span::SpanTree` for every element,
+/// so that each node can be traced back to the wikitext it came from.
+pub fn parse_with_spans(s: String) -> Result<(Vec<ast::TreeElement>, Vec<span::SpanTree>), error::ParseError> {
+  parse_document(s, &mut vec![])
+}
+
+/// Lists markup that the parser accepts but probably misreads, ordered by position.
+pub fn lint(s: String) -> Result<Vec<lint::Warning>, error::ParseError> {
+  let mut warnings = vec![];
+  parse_document(s, &mut warnings)?;
+  warnings.sort_by_key(|v| v.span.start);
+  Ok(warnings)
+}
 
-  let token = tokenizer::tokenize(s);
+fn parse_document(s: String, warnings: &mut Vec<lint::Warning>) -> Result<(Vec<ast::TreeElement>, Vec<span::SpanTree>), error::ParseError> {
+  let token = tokenizer::tokenize(s, warnings); // CRLF, CRはLFとして読む
   //println!("{:#?}", token);
-  let block_tree = block::parse(token);
+  let (block_tree, spans) = block::parse(token, warnings)?;
   //println!("{:#?}", block_tree);
-  let ast = inline::parse(block_tree);
-  //println!("{:#?}", ast);
-  let html = renderer::xhtml::render(ast);
+  inline::parse(block_tree, spans, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use error::{Error, ParseError, Position, MAX_NESTING_DEPTH};
+
+  #[test]
+  fn test_unterminated_element() {
+    assert_eq!(parse_to_ast(String::from("a\r\n**b** [[span class=\"x\"")), Ok(vec![ast::TreeElement::Paragraph(vec![
+      ast::TreeElement::Text(String::from("a")), ast::TreeElement::NewLine,
+      ast::TreeElement::Bold(vec![ast::TreeElement::Text(String::from("b"))]), ast::TreeElement::Text(String::from(" [[span class=\"x\"")),
+    ])]));
+  }
+
+  #[test]
+  fn test_nesting_too_deep() {
+    let deep_quote = ">".repeat(MAX_NESTING_DEPTH + 1) + " a";
+    let position = Position { offset: 0, line: 1, column: 1 };
+    assert_eq!(parse(deep_quote), Err(Error::Parse(ParseError::NestingTooDeep { limit: MAX_NESTING_DEPTH, position })));
+
+    let deep_span = "[[span]]".repeat(MAX_NESTING_DEPTH + 1);
+    let position = Position { offset: 8 * MAX_NESTING_DEPTH, line: 1, column: 8 * MAX_NESTING_DEPTH + 1 };
+    assert_eq!(parse(deep_span), Err(Error::Parse(ParseError::NestingTooDeep { limit: MAX_NESTING_DEPTH, position })));
+
+    assert!(parse(">".repeat(MAX_NESTING_DEPTH) + " a").is_ok());
+  }
+
+  #[test]
+  fn test_arbitrary_input_does_not_panic() {
+    const PIECES: [&str; 28] = [
+      "*", "/", "_", "-", "{", "}", "^", ",", "[", "]", "|", "#", ">", "+", "@", "\\", "~", "<", "=", "\"",
+      "\n", " ", "a", "[[span]]", "[[/span]]", "[[footnote]]", "##red|", "[[code]]",
+    ];
+
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    for _ in 0..2000 {
+      let mut s = String::new();
+      for _ in 0..40 {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); // LCG
+        s += PIECES[(seed >> 33) as usize % PIECES.len()];
+      }
+      if let Ok((ast, spans)) = parse_with_spans(s) {
+        assert_mirrors(&ast.iter().collect::<Vec<_>>(), &spans);
+      }
+    }
+  }
 
-  html
+  fn assert_mirrors(elements: &[&ast::TreeElement], spans: &[span::SpanTree]) {
+    assert_eq!(elements.len(), spans.len());
+    for (element, span_tree) in elements.iter().zip(spans) {
+      assert!(span_tree.span.start <= span_tree.span.end);
+      assert_mirrors(&element.children(), &span_tree.children);
+    }
+  }
 }
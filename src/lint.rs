@@ -0,0 +1,143 @@
+use crate::ast::ParseFrameKind;
+use crate::span::Span;
+
+/// Markup that parses, but probably not the way the author meant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+  pub kind: WarningKind,
+  pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarningKind {
+  /// `markup` (`**`, `{{`, `[[div]]`, ...) is closed implicitly at the end of its paragraph or block.
+  Unclosed{markup: String},
+  /// `markup` (`[[/span]]`, `}}`, `##`, ...) has nothing open to close and is dropped.
+  UnmatchedClose{markup: String},
+  /// `[[name]]` is not an element this parser knows; it is dropped.
+  UnknownElement{name: String},
+  /// `[[size value]]` is not a number with `%`, `em`, `rem`, `px` or `pt`, nor a size keyword; the wrapper is dropped and its text is kept.
+  InvalidSize{value: String},
+  /// `[[` is never closed with `]]`; it is read as text.
+  UnterminatedElement,
+  /// A link without a target.
+  EmptyLink,
+  /// A table row spans `found` columns while the first row spans `expected`.
+  InconsistentColumns{expected: usize, found: usize},
+}
+
+impl std::fmt::Display for Warning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: ", self.span.start)?;
+    match &self.kind {
+      WarningKind::Unclosed { markup } => write!(f, "`{}` is never closed", markup),
+      WarningKind::UnmatchedClose { markup } => write!(f, "`{}` closes nothing", markup),
+      WarningKind::UnknownElement { name } => write!(f, "unknown element `[[{}]]`", name),
+      WarningKind::InvalidSize { value } => write!(f, "`{}` is not a font size", value),
+      WarningKind::UnterminatedElement => write!(f, "`[[` is never closed with `]]`"),
+      WarningKind::EmptyLink => write!(f, "link has no target"),
+      WarningKind::InconsistentColumns { expected, found } => write!(f, "table row has {} columns, the first row has {}", found, expected),
+    }
+  }
+}
+
+/// The markup that opens an inline frame of `kind`.
+pub(crate) fn opening_markup(kind: &ParseFrameKind) -> String {
+  String::from(match kind {
+    ParseFrameKind::Bold => "**",
+    ParseFrameKind::Italics => "//",
+    ParseFrameKind::Underline => "__",
+    ParseFrameKind::Strikethrough => "--",
+    ParseFrameKind::Monospaced => "{{",
+    ParseFrameKind::Superscript => "^^",
+    ParseFrameKind::Subscript => ",,",
+    ParseFrameKind::Colored => "##",
+    ParseFrameKind::Size => "[[size]]",
+    ParseFrameKind::Footnote => "[[footnote]]",
+    ParseFrameKind::HtmlElement { tag } => return format!("[[{}]]", tag),
+    ParseFrameKind::Paragraph | ParseFrameKind::Collapsible | ParseFrameKind::QuoteBlock | ParseFrameKind::Tab
+      | ParseFrameKind::TabView | ParseFrameKind::List | ParseFrameKind::ListItem | ParseFrameKind::Align => "", // block frames never reach the inline builder
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lint(s: &str) -> Vec<(WarningKind, usize, usize)> {
+    crate::lint(String::from(s)).unwrap().into_iter().map(|v| (v.kind, v.span.start.line, v.span.start.column)).collect()
+  }
+
+  fn unclosed(markup: &str) -> WarningKind {
+    WarningKind::Unclosed { markup: String::from(markup) }
+  }
+
+  fn unmatched(markup: &str) -> WarningKind {
+    WarningKind::UnmatchedClose { markup: String::from(markup) }
+  }
+
+  #[test]
+  fn test_clean() {
+    assert_eq!(lint("**a** //b// {{c}}\n[[span class=\"x\"]]d[[/span]]\n||a||b||\n||c||d||"), vec![]);
+  }
+
+  #[test]
+  fn test_unclosed_inline() {
+    assert_eq!(lint("a **b\nc //d {{e"), vec![
+      (unclosed("**"), 1, 3),
+      (unclosed("//"), 2, 3),
+      (unclosed("{{"), 2, 7),
+    ]);
+  }
+
+  #[test]
+  fn test_unmatched_close() {
+    assert_eq!(lint("a[[/span]] b ##\n\n[[/div]]"), vec![
+      (unmatched("[[/span]]"), 1, 2),
+      (unmatched("##"), 1, 14),
+      (unmatched("[[/div]]"), 3, 1),
+    ]);
+  }
+
+  #[test]
+  fn test_unknown_element() {
+    assert_eq!(lint("a [[foo]] b"), vec![(WarningKind::UnknownElement { name: String::from("foo") }, 1, 3)]);
+    assert_eq!(lint("+ a [[div]]\nb\n[[/div]]"), vec![]);
+  }
+
+  #[test]
+  fn test_invalid_size() {
+    assert_eq!(lint("[[size 1em;color:red]]a[[/size]]"), vec![
+      (WarningKind::InvalidSize { value: String::from("1em;color:red") }, 1, 1),
+    ]);
+    assert_eq!(lint("[[size -1em]]x[[/size]]"), vec![(WarningKind::InvalidSize { value: String::from("-1em") }, 1, 1)]);
+  }
+
+  #[test]
+  fn test_unterminated_element() {
+    assert_eq!(lint("a\n[[span **b**"), vec![(WarningKind::UnterminatedElement, 2, 1)]);
+  }
+
+  #[test]
+  fn test_empty_link() {
+    assert_eq!(lint("[[[ ]]] [ x]"), vec![(WarningKind::EmptyLink, 1, 1), (WarningKind::EmptyLink, 1, 9)]);
+  }
+
+  #[test]
+  fn test_inconsistent_columns() {
+    assert_eq!(lint("||a||b||\n||c||\n||~ d||~ e||"), vec![
+      (WarningKind::InconsistentColumns { expected: 2, found: 1 }, 2, 3),
+    ]);
+  }
+
+  #[test]
+  fn test_unclosed_block() {
+    assert_eq!(lint("a\n[[div]]\nb"), vec![(unclosed("[[div]]"), 2, 1)]);
+  }
+
+  #[test]
+  fn test_display() {
+    let warnings = crate::lint(String::from("x\n**a")).unwrap();
+    assert_eq!(warnings[0].to_string(), "2:1: `**` is never closed");
+  }
+}
@@ -1,15 +1,16 @@
-fn read<T: std::str::FromStr>() -> T {
+fn read() -> Option<String> {
     let mut s = String::new();
-    std::io::stdin().read_line(&mut s).ok();
-    s.trim().parse().ok().unwrap()
+    match std::io::stdin().read_line(&mut s) {
+        Ok(0) | Err(_) => None, // end of input
+        Ok(_) => Some(String::from(s.trim_end_matches(['\n', '\r']))),
+    }
 }
 
 fn main() {
     // get input
     let mut input = String::new();
 
-    loop {
-        let s: String = read();
+    while let Some(s) = read() {
         if s == "EOF" {
             break;
         } else {
@@ -18,6 +19,11 @@ fn main() {
         }
     }
 
-    let output = wikidot_parser::parse(input);
-    println!("{}", output);
+    match wikidot_parser::parse(input) {
+        Ok(output) => println!("{}", output),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
 }
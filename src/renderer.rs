@@ -1 +1,11 @@
-pub mod xhtml;
\ No newline at end of file
+pub mod xhtml;
+pub mod wikidot;
+pub mod markdown;
+pub mod plaintext;
+pub mod whitelist;
+pub mod resolver;
+
+/// Deepest tree the renderers walk before giving up with `RenderError::NestingTooDeep`.
+/// The parser caps block-level frames and the inline frames inside them at `MAX_NESTING_DEPTH` each,
+/// so every parsed page fits; only a hand-built AST can reach this.
+pub(crate) const MAX_RENDER_DEPTH: usize = 2 * crate::error::MAX_NESTING_DEPTH;
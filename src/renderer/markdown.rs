@@ -0,0 +1,648 @@
+use crate::ast::{table_cell, Alignment, Float, ListKind, TreeElement};
+use crate::error::RenderError;
+use super::xhtml::builder::Builder;
+
+pub struct RenderOptions<'a> {
+  pub attribute_whitelist: super::whitelist::AttributeWhitelist,
+  pub link_resolver: &'a dyn super::resolver::LinkResolver,
+  pub image_source_resolver: &'a dyn super::resolver::ImageSourceResolver,
+}
+
+impl Default for RenderOptions<'_> {
+  fn default() -> Self {
+    Self {
+      attribute_whitelist: super::whitelist::AttributeWhitelist::default(),
+      link_resolver: &super::resolver::WikidotLinkResolver,
+      image_source_resolver: &super::resolver::WikidotImageSourceResolver { page: None },
+    }
+  }
+}
+
+pub fn render(ast: Vec<TreeElement>) -> Result<String, RenderError> {
+  render_with_options(ast, &RenderOptions::default())
+}
+
+/// Exports the page as GitHub-flavoured Markdown. What Markdown has no syntax for (underline, colours, sizes,
+/// superscripts, collapsible blocks, tab views, ...) is written as inline HTML.
+pub fn render_with_options(ast: Vec<TreeElement>, options: &RenderOptions) -> Result<String, RenderError> {
+  let mut w = Writer {
+    options,
+    res: String::new(),
+    prefix: String::new(),
+    line_start: 0,
+    single_line: false,
+    footnotes: super::xhtml::collect_footnotes(&ast),
+    footnotes_written: false,
+    depth: 0,
+  };
+  w.blocks(&ast)?;
+
+  // [[footnoteblock]]のないASTでも脚注の定義は書く
+  if !w.footnotes_written && !w.footnotes.is_empty() {
+    if !w.res.is_empty() {
+      w.blank_line();
+    }
+    w.footnote_definitions()?;
+  }
+  Ok(w.res)
+}
+
+/// `<tag key="value">` with the attributes filtered by the whitelist.
+fn open_tag(tag: &str, attributes: &[(String, String)]) -> String {
+  let mut res = format!("<{}", tag);
+  for (key, value) in attributes {
+    res += &format!(" {}=\"{}\"", key, Builder::escape_chars(value));
+  }
+  res.push('>');
+  res
+}
+
+/// A link destination, in `<>` when it has spaces.
+fn destination(href: &str) -> String {
+  let mut res = String::new();
+  for c in href.chars() {
+    if matches!(c, '\\' | '(' | ')' | '<' | '>' | '|') { // "|"は表のセルを分けてしまう
+      res.push('\\');
+    }
+    res.push(c);
+  }
+  if res.is_empty() || res.contains(char::is_whitespace) {
+    res = format!("<{}>", res);
+  }
+  res
+}
+
+/// The longest run of backticks in `s`.
+fn longest_backticks(s: &str) -> usize {
+  let mut longest = 0;
+  let mut run = 0;
+  for c in s.chars() {
+    run = if c == '`' { run + 1 } else { 0 };
+    longest = longest.max(run);
+  }
+  longest
+}
+
+/// `` `code` `` with a fence longer than any run of backticks inside.
+fn code_span(code: &str) -> String {
+  let fence = "`".repeat(longest_backticks(code) + 1);
+  // 両端の空白は1つずつ取り除かれる
+  let pad = code.starts_with('`') || code.ends_with('`') || (code.starts_with(' ') && code.ends_with(' ') && code.trim() != "");
+  let pad = if pad { " " } else { "" };
+  format!("{}{}{}{}{}", fence, pad, code, pad, fence)
+}
+
+struct Writer<'o, 'a> {
+  options: &'o RenderOptions<'a>,
+  res: String,
+  prefix: String, // 引用の"> "とリストの字下げ
+  line_start: usize, // 今の行の, prefixやリストの記号を除いた始まり
+  single_line: bool, // 表のセルと見出しは改行できない
+  footnotes: Vec<(u32, Vec<TreeElement>)>,
+  footnotes_written: bool,
+  depth: usize,
+}
+
+impl Writer<'_, '_> {
+  fn newline(&mut self) {
+    self.res.push('\n');
+    self.res += &self.prefix;
+    self.line_start = self.res.len();
+  }
+
+  fn blank_line(&mut self) {
+    self.res.push('\n');
+    self.res += self.prefix.trim_end();
+    self.newline();
+  }
+
+  fn write_text(&mut self, text: &str) {
+    for c in text.chars() {
+      if c == '\n' {
+        if self.single_line {
+          self.res.push(' ');
+        } else {
+          self.newline();
+        }
+        continue;
+      }
+
+      let line = &self.res[self.line_start..];
+      if c == ' ' && line.is_empty() { // 行頭の字下げはコードブロックになる
+        continue;
+      }
+      let escape = match c {
+        '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '&' | '~' | '|' => true,
+        '#' | '+' | '-' | '=' => line.is_empty(), // 見出しやリスト
+        '.' | ')' => !line.is_empty() && line.chars().all(|c| c.is_ascii_digit()), // 番号付きリスト
+        _ => false,
+      };
+
+      if escape {
+        self.res.push('\\');
+      }
+      self.res.push(c);
+    }
+  }
+
+  fn blocks(&mut self, elements: &[TreeElement]) -> Result<(), RenderError> {
+    for (at, v) in elements.iter().enumerate() {
+      if at > 0 {
+        self.blank_line();
+      }
+      self.element(v)?;
+    }
+    Ok(())
+  }
+
+  fn inline(&mut self, elements: &[TreeElement]) -> Result<(), RenderError> {
+    for v in elements {
+      self.element(v)?;
+    }
+    Ok(())
+  }
+
+  /// Emphasis delimiters. They only work next to non-space characters, so surrounding spaces are moved outside.
+  fn wrap(&mut self, delimiter: &str, children: &[TreeElement]) -> Result<(), RenderError> {
+    let start = self.res.len();
+    self.inline(children)?;
+    let inner = self.res.split_off(start);
+
+    let body = inner.trim_matches(' ');
+    if body.is_empty() {
+      self.res += &inner;
+      return Ok(());
+    }
+    if self.line_start > start {
+      self.line_start += delimiter.len();
+    }
+    let leading = inner.len() - inner.trim_start_matches(' ').len();
+    self.res += &inner[..leading];
+    self.res += delimiter;
+    self.res += body;
+    self.res += delimiter;
+    self.res += &inner[leading + body.len()..];
+    Ok(())
+  }
+
+  fn html_wrap(&mut self, tag: &str, attributes: &[(String, String)], children: &[TreeElement]) -> Result<(), RenderError> {
+    self.res += &open_tag(tag, attributes);
+    self.inline(children)?;
+    self.res += &format!("</{}>", tag);
+    Ok(())
+  }
+
+  /// An HTML block tag around Markdown blocks, which need blank lines around them to be parsed.
+  fn html_block(&mut self, open: &str, children: &[TreeElement], close: &str) -> Result<(), RenderError> {
+    self.res += open;
+    if !children.is_empty() {
+      self.blank_line();
+      self.blocks(children)?;
+    }
+    self.blank_line();
+    self.res += close;
+    Ok(())
+  }
+
+  fn details(&mut self, summary: &str, open: bool, children: &[TreeElement]) -> Result<(), RenderError> {
+    let open_tag = if open { "<details open>" } else { "<details>" };
+    self.html_block(&format!("{}\n{}<summary>{}</summary>", open_tag, self.prefix, Builder::escape_chars(summary)), children, "</details>")
+  }
+
+  fn list(&mut self, kind: ListKind, items: &[TreeElement]) -> Result<(), RenderError> {
+    for (at, item) in items.iter().enumerate() {
+      if at > 0 {
+        self.newline();
+      }
+      let marker = match kind {
+        ListKind::Unordered => String::from("- "),
+        ListKind::Ordered => format!("{}. ", at + 1),
+      };
+      self.res += &marker;
+      self.line_start = self.res.len();
+
+      // 続く行は記号の幅だけ字下げする
+      let outer = self.prefix.len();
+      self.prefix += &" ".repeat(marker.len());
+      let children = match item {
+        TreeElement::ListItem(children) => children.as_slice(),
+        v => std::slice::from_ref(v), // Lists only hold ListItems
+      };
+      for child in children {
+        if let TreeElement::List { kind, children } = child {
+          self.newline();
+          self.enter()?;
+          self.list(*kind, children)?;
+          self.depth -= 1;
+        } else {
+          self.element(child)?;
+        }
+      }
+      self.prefix.truncate(outer);
+    }
+    Ok(())
+  }
+
+  /// A GFM table. The header row is empty unless the first row only has title cells;
+  /// cells spanning several columns are followed by empty ones.
+  fn table(&mut self, rows: &[Vec<table_cell::Cell>]) -> Result<(), RenderError> {
+    use table_cell::Style;
+
+    let columns = rows.iter().map(|row| row.iter().map(|v| v.spanning.get()).sum::<usize>()).max().unwrap_or(0);
+    if columns == 0 {
+      return Ok(());
+    }
+    let (header, body) = match rows.split_first() {
+      Some((first, rest)) if first.iter().all(|v| v.style == Some(Style::Title)) => (Some(first), rest),
+      _ => (None, rows),
+    };
+
+    // 列の揃え方は最初に揃え方を指定したセルに従う
+    let mut alignments = vec![None; columns];
+    for row in body {
+      let mut column = 0;
+      for cell in row {
+        if alignments[column].is_none() && matches!(cell.style, Some(Style::LeftAligned | Style::RightAligned | Style::CenterAligned)) {
+          alignments[column] = cell.style;
+        }
+        column += cell.spanning.get();
+      }
+    }
+
+    let single_line = std::mem::replace(&mut self.single_line, true);
+    match header {
+      Some(row) => self.table_row(row, columns)?,
+      None => self.res += &format!("{}|", "| ".repeat(columns)),
+    }
+    self.newline();
+    for alignment in alignments {
+      self.res += match alignment {
+        Some(Style::LeftAligned) => "| :-- ",
+        Some(Style::RightAligned) => "| --: ",
+        Some(Style::CenterAligned) => "| :-: ",
+        _ => "| --- ",
+      };
+    }
+    self.res.push('|');
+    for row in body {
+      self.newline();
+      self.table_row(row, columns)?;
+    }
+    self.single_line = single_line;
+    Ok(())
+  }
+
+  fn table_row(&mut self, row: &[table_cell::Cell], columns: usize) -> Result<(), RenderError> {
+    let mut written = 0;
+    for cell in row {
+      self.res += "| ";
+      self.line_start = self.res.len();
+      self.inline(&cell.val)?;
+      self.res.truncate(self.res.trim_end_matches(' ').len().max(self.line_start));
+      self.res.push(' ');
+      self.res += &"| ".repeat(cell.spanning.get() - 1);
+      written += cell.spanning.get();
+    }
+    self.res += &"| ".repeat(columns - written);
+    self.res.push('|');
+    Ok(())
+  }
+
+  fn footnote_definitions(&mut self) -> Result<(), RenderError> {
+    self.footnotes_written = true;
+    let footnotes = std::mem::take(&mut self.footnotes);
+    for (at, (id, children)) in footnotes.iter().enumerate() {
+      if at > 0 {
+        self.newline();
+      }
+      self.res += &format!("[^{}]: ", id);
+      self.line_start = self.res.len();
+      let outer = self.prefix.len();
+      self.prefix += "    "; // 続く行は定義の中に字下げする
+      self.inline(children)?;
+      self.prefix.truncate(outer);
+    }
+    self.footnotes = footnotes;
+    Ok(())
+  }
+
+  fn enter(&mut self) -> Result<(), RenderError> {
+    self.depth += 1;
+    if self.depth > super::MAX_RENDER_DEPTH {
+      return Err(RenderError::NestingTooDeep { limit: super::MAX_RENDER_DEPTH });
+    }
+    Ok(())
+  }
+
+  fn element(&mut self, v: &TreeElement) -> Result<(), RenderError> {
+    self.enter()?;
+
+    match v {
+      TreeElement::Paragraph(children) => self.inline(children)?,
+      TreeElement::Text(text) => self.write_text(text),
+      TreeElement::Bold(children) => self.wrap("**", children)?,
+      TreeElement::Italics(children) => self.wrap("*", children)?,
+      TreeElement::Strikethrough(children) => self.wrap("~~", children)?,
+      TreeElement::Underline(children) => self.html_wrap("u", &[], children)?,
+      TreeElement::Superscript(children) => self.html_wrap("sup", &[], children)?,
+      TreeElement::Subscript(children) => self.html_wrap("sub", &[], children)?,
+
+      TreeElement::Monospaced(children) => {
+        // コードスパンの中には書式を書けない
+        let texts: Option<Vec<&str>> = children.iter().map(|v| match v {
+          TreeElement::Text(text) => Some(text.as_str()),
+          _ => None,
+        }).collect();
+        match texts {
+          Some(texts) if !texts.concat().is_empty() => {
+            let code = texts.concat().replace('\n', " ");
+            let code = if self.single_line { code.replace('|', "\\|") } else { code };
+            self.res += &code_span(&code);
+          }
+          _ => self.html_wrap("code", &[], children)?,
+        }
+      }
+
+      TreeElement::Colored { red, green, blue, children } => {
+        self.html_wrap("span", &[(String::from("style"), format!("color: rgb({}, {}, {})", red, green, blue))], children)?;
+      }
+
+      TreeElement::Size { scale, children } => {
+        self.html_wrap("span", &[(String::from("style"), format!("font-size: {}", scale))], children)?;
+      }
+
+      TreeElement::Link { href, name, .. } => { // 新しいタブで開くかはMarkdownでは指定できない
+        let text = if name.is_empty() { &href.0 } else { name };
+        match super::resolver::external_link(&href.0) {
+          Some(href) => {
+            self.res.push('[');
+            self.write_text(text);
+            self.res += &format!("]({})", destination(&href));
+          }
+          None => self.write_text(text), // 危ないスキームのリンクは名前だけ書く
+        }
+      }
+
+      TreeElement::InternalLink { href, name, .. } => {
+        let text = if name.is_empty() { self.options.link_resolver.link_text(href) } else { name.clone() };
+        self.res.push('[');
+        self.write_text(&text);
+        self.res += &format!("]({})", destination(&self.options.link_resolver.resolve(href)));
+      }
+
+      TreeElement::Collapsible { show, folded, children, .. } => self.details(show, !folded, children)?,
+
+      TreeElement::Footnote { id, .. } => self.res += &format!("[^{}]", id),
+
+      TreeElement::FootnoteBlock { .. } => { // 脚注はページの末尾に表示されるので見出しは書かない
+        if !self.footnotes_written {
+          self.footnote_definitions()?;
+        }
+      }
+
+      TreeElement::QuoteBlock(children) => {
+        self.res += "> ";
+        self.prefix += "> ";
+        self.line_start = self.res.len();
+        self.blocks(children)?;
+        self.prefix.truncate(self.prefix.len() - 2);
+      }
+
+      TreeElement::TableOfContents { .. } => {} // GitHubは見出しから目次を作る
+
+      TreeElement::Align { alignment, children } => { // GitHubはstyle属性を消すがalignは残す
+        self.html_block(&format!("<div align=\"{}\">", alignment.css_value()), children, "</div>")?;
+      }
+
+      TreeElement::DefinitionList(items) => {
+        // GFMに定義リストはない. HTMLブロックの中のMarkdownは空行で挟むと解釈される
+        self.res += "<dl>";
+        for (term, definition) in items {
+          for (tag, children) in [("dt", term), ("dd", definition)] {
+            self.newline();
+            self.res += &format!("<{}>", tag);
+            self.blank_line();
+            self.inline(children)?;
+            self.blank_line();
+            self.res += &format!("</{}>", tag);
+          }
+        }
+        self.newline();
+        self.res += "</dl>";
+      }
+
+      TreeElement::HorizontalRule => self.res += "---",
+
+      TreeElement::ClearFloat(float) => {
+        let clear = match float {
+          Some(Float::Left) => "left",
+          Some(Float::Right) => "right",
+          None => "both",
+        };
+        self.res += &format!("<div style=\"clear: {}\"></div>", clear);
+      }
+
+      TreeElement::Image { source, alt, title, width, height, link, alignment, class, style, .. } => {
+        let src = self.options.image_source_resolver.resolve(source);
+        let alt = alt.clone().unwrap_or_else(|| String::from(source.rsplit('/').next().unwrap_or_default()));
+        let href = link.as_deref().and_then(|v| super::resolver::image_link(v, self.options.link_resolver));
+
+        if width.is_some() || height.is_some() || alignment.is_some() || class.is_some() || style.is_some() {
+          // Markdownの画像は大きさや配置を指定できない
+          let mut attributes = vec![(String::from("src"), src), (String::from("alt"), alt)];
+          let align = alignment.map(|v| String::from(match v {
+            Alignment::Left => "left",
+            Alignment::Right => "right",
+            Alignment::Center => "center",
+          }));
+          let style = style.as_deref().map(|v| self.options.attribute_whitelist.filter_style(v)).filter(|v| !v.is_empty());
+          let optional = [("title", title.clone()), ("width", width.map(|v| v.to_string())), ("height", height.map(|v| v.to_string())),
+            ("align", align), ("class", class.clone()), ("style", style)];
+          for (key, value) in optional {
+            if let Some(value) = value {
+              attributes.push((String::from(key), value));
+            }
+          }
+          let image = open_tag("img", &attributes);
+          match href {
+            Some(href) => self.res += &format!("{}{}</a>", open_tag("a", &[(String::from("href"), href)]), image),
+            None => self.res += &image,
+          }
+        } else {
+          if href.is_some() {
+            self.res.push('[');
+          }
+          self.res += "![";
+          self.write_text(&alt);
+          self.res += &format!("]({}", destination(&src));
+          if let Some(title) = title {
+            self.res += &format!(" \"{}\"", title.replace('\\', "\\\\").replace('"', "\\\""));
+          }
+          self.res.push(')');
+          if let Some(href) = href {
+            self.res += &format!("]({})", destination(&href));
+          }
+        }
+      }
+
+      TreeElement::Iframe(raw) => self.res += &format!("<iframe srcdoc=\"{}\"></iframe>", Builder::escape_chars(raw)),
+
+      TreeElement::CodeBlock { language, source } => {
+        if self.single_line {
+          self.res += &code_span(&source.replace('\n', " ").replace('|', "\\|"));
+        } else {
+          let fence = "`".repeat(3.max(longest_backticks(source) + 1));
+          if self.res.len() != self.line_start {
+            self.newline();
+          }
+          self.res += &fence;
+          self.res += language.as_deref().unwrap_or("");
+          for line in source.lines() {
+            self.newline();
+            self.res += line;
+          }
+          self.newline();
+          self.res += &fence;
+        }
+      }
+
+      TreeElement::Tab { title, children } => self.details(title, true, children)?,
+
+      TreeElement::TabView(children) => {
+        for (at, tab) in children.iter().enumerate() {
+          if at > 0 {
+            self.blank_line();
+          }
+          match tab {
+            TreeElement::Tab { title, children } => { // 最初のタブだけ開いておく
+              self.enter()?;
+              self.details(title, at == 0, children)?;
+              self.depth -= 1;
+            }
+            v => self.element(v)?, // TabViews only hold Tabs
+          }
+        }
+      }
+
+      TreeElement::Table(rows) => self.table(rows)?,
+
+      TreeElement::Heading { level, children, .. } => {
+        self.res += &"#".repeat(usize::from((*level).clamp(1, 6)));
+        self.res.push(' ');
+        self.line_start = self.res.len();
+        let single_line = std::mem::replace(&mut self.single_line, true);
+        self.inline(children)?;
+        self.single_line = single_line;
+      }
+
+      TreeElement::List { kind, children } => self.list(*kind, children)?,
+      TreeElement::ListItem(_) => self.list(ListKind::Unordered, std::slice::from_ref(v))?, // outside of a List
+
+      TreeElement::NewLine => {
+        if self.single_line {
+          self.res += "<br>";
+        } else {
+          self.res.push('\\'); // 行末の"\"は強制改行
+          self.newline();
+        }
+      }
+
+      TreeElement::HtmlElement { tag, property, children } => {
+        if !super::xhtml::is_allowed_tag(tag) {
+          return Err(RenderError::InvalidTagName { tag: tag.clone() });
+        }
+        let property = self.options.attribute_whitelist.filter(property);
+        if tag == "div" && !self.single_line {
+          self.html_block(&open_tag(tag, &property), children, "</div>")?;
+        } else {
+          self.html_wrap(tag, &property, children)?;
+        }
+      }
+    }
+
+    self.depth -= 1;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(s: &str) -> String {
+    super::render(crate::parse_to_ast(String::from(s)).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn test_inline_markup() {
+    assert_eq!(render("**a** //b// --c-- {{d}} {{**e**}}"), "**a** *b* ~~c~~ `d` <code>**e**</code>");
+    assert_eq!(render("__a__ ^^b^^ ,,c,, ##red|d##"),
+      "<u>a</u> <sup>b</sup> <sub>c</sub> <span style=\"color: rgb(255, 0, 0)\">d</span>");
+    assert_eq!(render("[[size 80%]]e[[/size]]"), "<span style=\"font-size: 80%\">e</span>");
+    assert_eq!(render("a** b **c"), "a **b** c");
+    assert_eq!(render("a\nb"), "a\\\nb");
+  }
+
+  #[test]
+  fn test_links() {
+    assert_eq!(render("[https://example.com/a_(b) x] [[[Some Page]]] [[[page|name]]]"),
+      "[x](https://example.com/a_\\(b\\)) [Some Page](/some-page) [name](/page)");
+    assert_eq!(render("[javascript:alert(1) a] [data:text/html,x b] [mailto:a@example.com c]"), "a b [c](mailto:a@example.com)");
+    let link = TreeElement::Link { href: crate::ast::Url(String::from(" JaVaScRiPt:alert(1)")), open_in_new_tab: false, name: String::from("d") };
+    assert_eq!(super::render(vec![TreeElement::Paragraph(vec![link])]).unwrap(), "d");
+  }
+
+  #[test]
+  fn test_image() {
+    assert_eq!(render("[[image a.png]] [[image /p/b.png alt=\"B *c*\" title='say \"hi\"' link=\"page\"]]"),
+      "![a.png](a.png) [![B \\*c\\*](/local--files/p/b.png \"say \\\"hi\\\"\")](/page)");
+    assert_eq!(render("[[f>image a.png width=\"100\" link=\"https://example.com\"]]"),
+      "<a href=\"https://example.com\"><img src=\"a.png\" alt=\"a.png\" width=\"100\" align=\"right\"></a>");
+  }
+
+  #[test]
+  fn test_escape() {
+    let text = |s: &str| super::render(vec![TreeElement::Paragraph(vec![TreeElement::Text(String::from(s))])]).unwrap();
+
+    assert_eq!(text("*a* _b_ [c](d) <e> & `f`"), "\\*a\\* \\_b\\_ \\[c\\](d) \\<e\\> \\& \\`f\\`");
+    assert_eq!(text("# a\n- b\n1. c\n  d"), "\\# a\n\\- b\n1\\. c\nd");
+  }
+
+  #[test]
+  fn test_blocks() {
+    assert_eq!(render("+ Title\n\n++* Sub\n\npara"), "# Title\n\n## Sub\n\npara");
+    assert_eq!(render("> a\n>> b\n> c"), "> a\n>\n> > b\n>\n> c");
+    assert_eq!(render("* a\n * b\n  # c\n  # d\n* e"), "- a\n  - b\n    1. c\n    2. d\n- e");
+    assert_eq!(render("[[code type=\"rust\"]]\nfn main() {}\n[[/code]]"), "```rust\nfn main() {}\n```");
+    assert_eq!(render("[[div class=\"box\"]]\na\n[[/div]]"), "<div class=\"box\">\n\na\n\n</div>");
+    assert_eq!(render(": **a** : b"), "<dl>\n<dt>\n\n**a**\n\n</dt>\n<dd>\n\nb\n\n</dd>\n</dl>");
+    assert_eq!(render("[[=]]\na\n[[/=]]"), "<div align=\"center\">\n\na\n\n</div>");
+    assert_eq!(render("a\n----\nb\n~~~~>"), "a\n\n---\n\nb\n\n<div style=\"clear: right\"></div>");
+  }
+
+  #[test]
+  fn test_table() {
+    assert_eq!(render("||~ a ||~ b ||~ c ||\n||< d ||> e ||= f ||\n|||| g || h ||"),
+      "| a | b | c |\n| :-- | --: | :-: |\n| d | e | f |\n| g | | h |");
+    assert_eq!(render("||a||b|c||\n||d||"), "| | |\n| --- | --- |\n| a | b\\|c |\n| d | |");
+  }
+
+  #[test]
+  fn test_footnotes() {
+    assert_eq!(render("a[[footnote]]b\nc[[/footnote]] d[[footnote]]e[[/footnote]]"),
+      "a[^1] d[^2]\n\n[^1]: b\\\n    c\n[^2]: e");
+  }
+
+  #[test]
+  fn test_details() {
+    assert_eq!(render("[[collapsible show=\"Open\"]]\na\n[[/collapsible]]"), "<details>\n<summary>Open</summary>\n\na\n\n</details>");
+    assert_eq!(render("[[tabview]]\n[[tab A]]\na\n[[/tab]]\n[[tab B]]\nb\n[[/tab]]\n[[/tabview]]"),
+      "<details open>\n<summary>A</summary>\n\na\n\n</details>\n\n<details>\n<summary>B</summary>\n\nb\n\n</details>");
+  }
+  #[test]
+  fn test_html_element() {
+    assert_eq!(render("[[div class=\"box\"]]\na\n[[/div]]"), "<div class=\"box\">\n\na\n\n</div>");
+    let ast = vec![TreeElement::HtmlElement { tag: String::from("script"), property: vec![], children: vec![] }];
+    assert_eq!(super::render(ast), Err(RenderError::InvalidTagName { tag: String::from("script") }));
+  }
+}
@@ -0,0 +1,241 @@
+use crate::ast::TreeElement;
+use crate::error::RenderError;
+
+pub struct RenderOptions<'a> {
+  /// Cuts the text at a word boundary and ends it with `…` so that it is at most this many characters long.
+  pub max_chars: Option<usize>,
+  pub link_resolver: &'a dyn super::resolver::LinkResolver,
+}
+
+impl Default for RenderOptions<'_> {
+  fn default() -> Self {
+    Self {
+      max_chars: None,
+      link_resolver: &super::resolver::WikidotLinkResolver,
+    }
+  }
+}
+
+pub fn render(ast: Vec<TreeElement>) -> Result<String, RenderError> {
+  render_with_options(ast, &RenderOptions::default())
+}
+
+/// The readable text of the page, for search indexes and previews. Blocks are separated by blank lines,
+/// table cells by tabs, links keep their text only, and footnotes are appended as `1. text`.
+pub fn render_with_options(ast: Vec<TreeElement>, options: &RenderOptions) -> Result<String, RenderError> {
+  let mut w = Writer { options, res: String::new(), depth: 0 };
+  w.blocks(&ast)?;
+
+  for (id, children) in super::xhtml::collect_footnotes(&ast) {
+    w.separate(|w| {
+      w.res += &format!("{}. ", id);
+      w.inline(&children)
+    })?;
+  }
+
+  let text = w.res.trim_end();
+  Ok(match options.max_chars {
+    Some(max_chars) => truncate(text, max_chars),
+    None => String::from(text),
+  })
+}
+
+/// Cuts `text` at the last whitespace that leaves room for `…`, or mid-word if the first word is already too long.
+fn truncate(text: &str, max_chars: usize) -> String {
+  if text.chars().count() <= max_chars {
+    return String::from(text);
+  }
+  if max_chars == 0 {
+    return String::new();
+  }
+
+  let end = text.char_indices().nth(max_chars - 1).map_or(text.len(), |(at, _)| at); // …の分を空けておく
+  let head = &text[..end];
+  // 次の文字が空白なら単語の途中ではない
+  let head = if text[end..].starts_with(char::is_whitespace) {
+    head
+  } else {
+    head.rfind(char::is_whitespace).map_or(head, |at| &head[..at])
+  };
+  format!("{}…", head.trim_end())
+}
+
+struct Writer<'o, 'a> {
+  options: &'o RenderOptions<'a>,
+  res: String,
+  depth: usize,
+}
+
+impl Writer<'_, '_> {
+  /// Runs `f` after a blank line, which is taken back if `f` writes nothing.
+  /// The first block inside a container follows the blank line before the container.
+  fn separate(&mut self, f: impl FnOnce(&mut Self) -> Result<(), RenderError>) -> Result<(), RenderError> {
+    let start = self.res.len();
+    if !self.res.is_empty() && !self.res.ends_with("\n\n") {
+      self.res += "\n\n";
+    }
+    let written = self.res.len();
+    f(self)?;
+    if self.res.len() == written {
+      self.res.truncate(start);
+    }
+    Ok(())
+  }
+
+  fn blocks(&mut self, elements: &[TreeElement]) -> Result<(), RenderError> {
+    for v in elements {
+      self.separate(|w| w.element(v))?;
+    }
+    Ok(())
+  }
+
+  fn inline(&mut self, elements: &[TreeElement]) -> Result<(), RenderError> {
+    for v in elements {
+      self.element(v)?;
+    }
+    Ok(())
+  }
+
+  fn line(&mut self) {
+    if !self.res.is_empty() && !self.res.ends_with('\n') {
+      self.res.push('\n');
+    }
+  }
+
+  fn enter(&mut self) -> Result<(), RenderError> {
+    self.depth += 1;
+    if self.depth > super::MAX_RENDER_DEPTH {
+      return Err(RenderError::NestingTooDeep { limit: super::MAX_RENDER_DEPTH });
+    }
+    Ok(())
+  }
+
+  fn element(&mut self, v: &TreeElement) -> Result<(), RenderError> {
+    self.enter()?;
+
+    match v {
+      TreeElement::Text(text) => self.res += &text.replace('\n', " "), // HTMLでは空白と同じ
+      TreeElement::NewLine => self.res.push('\n'),
+
+      TreeElement::Paragraph(children)
+        | TreeElement::Bold(children)
+        | TreeElement::Italics(children)
+        | TreeElement::Underline(children)
+        | TreeElement::Strikethrough(children)
+        | TreeElement::Monospaced(children)
+        | TreeElement::Superscript(children)
+        | TreeElement::Subscript(children)
+        | TreeElement::Colored { children, .. }
+        | TreeElement::Size { children, .. }
+        | TreeElement::Heading { children, .. } => self.inline(children)?,
+
+      TreeElement::Link { name, .. } => self.res += name,
+      TreeElement::InternalLink { href, name, .. } => {
+        if name.is_empty() {
+          self.res += &self.options.link_resolver.link_text(href);
+        } else {
+          self.res += name;
+        }
+      }
+
+      TreeElement::Image { alt, .. } => self.res += alt.as_deref().unwrap_or_default(),
+
+      TreeElement::Footnote { .. } | TreeElement::FootnoteBlock { .. } => {} // 脚注は末尾にまとめて書く
+      TreeElement::Iframe(_) | TreeElement::TableOfContents { .. } | TreeElement::HorizontalRule | TreeElement::ClearFloat(_) => {}
+
+      TreeElement::Collapsible { children, .. }
+        | TreeElement::QuoteBlock(children)
+        | TreeElement::TabView(children)
+        | TreeElement::Align { children, .. } => self.blocks(children)?,
+
+      TreeElement::Tab { title, children } => {
+        self.res += title;
+        self.blocks(children)?;
+      }
+
+      TreeElement::CodeBlock { source, .. } => self.res += source,
+
+      TreeElement::Table(rows) => {
+        for row in rows {
+          self.line();
+          for (at, cell) in row.iter().enumerate() {
+            if at > 0 {
+              self.res.push('\t');
+            }
+            let start = self.res.len();
+            self.inline(&cell.val)?;
+            let text = self.res.split_off(start);
+            self.res += text.trim(); // "|| a ||"の空白は要らない
+          }
+        }
+      }
+
+      TreeElement::DefinitionList(items) => {
+        for (term, definition) in items {
+          self.line();
+          self.inline(term)?;
+          self.res += ": ";
+          self.inline(definition)?;
+        }
+      }
+
+      TreeElement::List { children, .. } => {
+        for item in children {
+          self.line();
+          self.element(item)?;
+        }
+      }
+
+      TreeElement::ListItem(children) => self.inline(children)?, // 入れ子のリストは次の行から
+
+      TreeElement::HtmlElement { tag, children, .. } => {
+        if tag == "div" {
+          self.blocks(children)?;
+        } else {
+          self.inline(children)?;
+        }
+      }
+    }
+
+    self.depth -= 1;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(s: &str) -> String {
+    super::render(crate::parse_to_ast(String::from(s)).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn test_blocks() {
+    assert_eq!(render("+ Title\n\n**a** //b//\nc\n\n> d\n\n* e\n * f\n* g"), "Title\n\na b\nc\n\nd\n\ne\nf\ng");
+    assert_eq!(render("||~ a ||~ b ||\n|| c |||| d ||"), "a\tb\nc\td");
+    assert_eq!(render("[[collapsible show=\"open\"]]\nhidden\n[[/collapsible]]\n\n[[code]]\nx < y\n[[/code]]"), "hidden\n\nx < y");
+    assert_eq!(render("[[tabview]]\n[[tab One]]\na\n[[/tab]]\n[[/tabview]]"), "One\n\na");
+    assert_eq!(render("a\n: b : **c**\n: d : e"), "a\n\nb: c\nd: e");
+  }
+
+  #[test]
+  fn test_links_and_footnotes() {
+    assert_eq!(render("[https://example.com site] [[[Some Page]]] [[[page|name]]]"), "site Some Page name");
+    assert_eq!(render("[[image a.png alt=\"A cat\"]] [[image b.png]]"), "A cat");
+    assert_eq!(render("a[[footnote]]b[[/footnote]] c[[footnote]]d[[/footnote]]\n\ne"), "a c\n\ne\n\n1. b\n\n2. d");
+  }
+
+  #[test]
+  fn test_truncate() {
+    assert_eq!(truncate("hello world", 11), "hello world");
+    assert_eq!(truncate("hello world", 10), "hello…");
+    assert_eq!(truncate("hello world", 7), "hello…");
+    assert_eq!(truncate("hello world", 6), "hello…");
+    assert_eq!(truncate("hello world", 5), "hell…");
+    assert_eq!(truncate("日本語 です", 5), "日本語…");
+
+    let options = RenderOptions { max_chars: Some(8), ..Default::default() };
+    assert_eq!(render_with_options(crate::parse_to_ast(String::from("a **bc** d\n\nefg")).unwrap(), &options).unwrap(), "a bc d…");
+  }
+}
@@ -0,0 +1,160 @@
+/// Turns the target of `[[[page|name]]]` into a URL.
+pub trait LinkResolver {
+  /// `page` is the target as written, e.g. `Category:Page Name#anchor`.
+  fn resolve(&self, page: &str) -> String;
+
+  /// Links to pages that do not exist are rendered with the `newpage` class.
+  fn exists(&self, _page: &str) -> bool {
+    true
+  }
+
+  /// Text of a link written without a name, e.g. `[[[page]]]`.
+  fn link_text(&self, page: &str) -> String {
+    match page.split_once('#') {
+      Some((page, _)) => String::from(page.trim()),
+      None => String::from(page.trim()),
+    }
+  }
+}
+
+/// Resolves pages to `/category:page-name#anchor` on the current site.
+/// `http://` and `https://` URLs and paths starting with `/` are passed through.
+/// Anything else, including URLs with other schemes such as `javascript://`, is read as a page name.
+pub struct WikidotLinkResolver;
+
+impl LinkResolver for WikidotLinkResolver {
+  fn resolve(&self, page: &str) -> String {
+    let page = page.trim();
+    if page.starts_with("http://") || page.starts_with("https://") || page.starts_with('/') {
+      return String::from(page);
+    }
+
+    match page.split_once('#') {
+      Some((name, anchor)) => format!("/{}#{}", normalize_page_name(name), anchor.trim()),
+      None => format!("/{}", normalize_page_name(page)),
+    }
+  }
+}
+
+/// Turns the source of `[[image source]]` into a URL.
+pub trait ImageSourceResolver {
+  fn resolve(&self, source: &str) -> String;
+}
+
+/// Resolves file names to the files attached to `page`, and `/other-page/file` to those of another page,
+/// under `/local--files/`. `http://` and `https://` URLs are passed through.
+/// Without a page, bare file names are left as relative URLs.
+pub struct WikidotImageSourceResolver<'a> {
+  pub page: Option<&'a str>,
+}
+
+impl ImageSourceResolver for WikidotImageSourceResolver<'_> {
+  fn resolve(&self, source: &str) -> String {
+    let source = source.trim();
+    if source.starts_with("http://") || source.starts_with("https://") {
+      return String::from(source);
+    }
+
+    match (source.strip_prefix('/').and_then(|v| v.split_once('/')), self.page) {
+      (Some((page, file)), _) => format!("/local--files/{}/{}", normalize_page_name(page), file),
+      (None, Some(page)) => format!("/local--files/{}/{}", normalize_page_name(page), source),
+      (None, None) => String::from(source),
+    }
+  }
+}
+
+/// The `href` of `[[image link="..."]]`. URLs, paths and anchors are kept and anything else is a page name.
+/// URLs with other schemes, such as `javascript://`, give no link.
+pub(crate) fn image_link(link: &str, resolver: &dyn LinkResolver) -> Option<String> {
+  let link = link.trim();
+  if link.starts_with("http://") || link.starts_with("https://") || link.starts_with('/') || link.starts_with('#') {
+    Some(String::from(link))
+  } else if link.contains("://") || link.is_empty() {
+    None
+  } else {
+    Some(resolver.resolve(link))
+  }
+}
+
+/// The `href` of `[url name]`. `http`, `https` and `mailto` URLs, relative URLs and anchors are kept;
+/// URLs with any other scheme, such as `javascript:` or `data:`, give no link.
+pub(crate) fn external_link(href: &str) -> Option<String> {
+  let href = href.trim();
+  // ブラウザはスキームの中のタブや改行を読み飛ばすので, 取り除いてからスキームを調べる
+  let compact: String = href.chars().filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control()).collect();
+  match compact.split_once(':').map(|(v, _)| v).filter(|v| !v.contains(['/', '?', '#'])) {
+    Some(scheme) if !["http", "https", "mailto"].contains(&scheme.to_ascii_lowercase().as_str()) => None,
+    _ => Some(String::from(href)),
+  }
+}
+
+/// Wikidot's page name normalisation: `Category: Some Page!` becomes `category:some-page`.
+/// The `_default` category is dropped.
+pub fn normalize_page_name(name: &str) -> String {
+  let parts: Vec<String> = name.split(':').map(|part| {
+    let mut res = String::new();
+    for c in part.trim().to_lowercase().chars() {
+      if c.is_alphanumeric() {
+        res.push(c);
+      } else if !res.is_empty() && !res.ends_with('-') {
+        res.push('-');
+      }
+    }
+    String::from(res.trim_end_matches('-'))
+  }).filter(|v| !v.is_empty()).collect();
+
+  match parts.as_slice() {
+    [category, page] if category == "default" => page.clone(), // `_default` loses its `_` above
+    _ => parts.join(":"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_normalize_page_name() {
+    assert_eq!(normalize_page_name("SCP-173"), "scp-173");
+    assert_eq!(normalize_page_name("  Some   Page Name! "), "some-page-name");
+    assert_eq!(normalize_page_name("Component: Image Block"), "component:image-block");
+    assert_eq!(normalize_page_name("_default:start"), "start");
+  }
+
+  #[test]
+  fn test_resolve() {
+    let resolver = WikidotLinkResolver;
+    assert_eq!(resolver.resolve("Some Page"), "/some-page");
+    assert_eq!(resolver.resolve("theme:Black Highlighter#toc2"), "/theme:black-highlighter#toc2");
+    assert_eq!(resolver.resolve("https://example.com/a"), "https://example.com/a");
+    assert_eq!(resolver.resolve("javascript://%0aalert(1)"), "/javascript:0aalert-1");
+    assert_eq!(resolver.link_text("Some Page#toc2"), "Some Page");
+  }
+
+  #[test]
+  fn test_image_source() {
+    let resolver = WikidotImageSourceResolver { page: Some("Some Page") };
+    assert_eq!(resolver.resolve("a.png"), "/local--files/some-page/a.png");
+    assert_eq!(resolver.resolve("/Other Page/b.png"), "/local--files/other-page/b.png");
+    assert_eq!(resolver.resolve("https://example.com/c.png"), "https://example.com/c.png");
+    assert_eq!(WikidotImageSourceResolver { page: None }.resolve("a.png"), "a.png");
+  }
+
+  #[test]
+  fn test_image_link() {
+    assert_eq!(image_link("Some Page", &WikidotLinkResolver).as_deref(), Some("/some-page"));
+    assert_eq!(image_link("https://example.com", &WikidotLinkResolver).as_deref(), Some("https://example.com"));
+    assert_eq!(image_link("javascript://%0aalert(1)", &WikidotLinkResolver), None);
+    assert_eq!(image_link("javascript:alert(1)", &WikidotLinkResolver).as_deref(), Some("/javascript:alert-1"));
+  }
+
+  #[test]
+  fn test_external_link() {
+    for href in ["https://example.com/a", "HTTP://example.com", "mailto:a@example.com", "/a:b", "page?x=a:b", "#a:b"] {
+      assert_eq!(external_link(href).as_deref(), Some(href));
+    }
+    for href in ["javascript:alert(1)", "data:text/html,x", " JaVaScRiPt:alert(1)", "java\tscript:alert(1)", "vbscript:x"] {
+      assert_eq!(external_link(href), None, "{}", href);
+    }
+  }
+}
@@ -0,0 +1,124 @@
+/// Attribute names and CSS properties allowed on `[[div]]` and `[[span]]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeWhitelist {
+  pub attributes: Vec<String>,
+  pub css_properties: Vec<String>,
+}
+
+impl Default for AttributeWhitelist {
+  fn default() -> Self {
+    let attributes = ["class", "id", "style", "title", "lang", "dir", "align"];
+    let css_properties = [
+      "color", "background", "background-color",
+      "font", "font-family", "font-size", "font-style", "font-variant", "font-weight",
+      "text-align", "text-decoration", "text-indent", "text-transform", "letter-spacing", "line-height", "white-space", "word-spacing", "vertical-align",
+      "border", "border-top", "border-right", "border-bottom", "border-left", "border-color", "border-style", "border-width", "border-radius", "border-collapse",
+      "margin", "margin-top", "margin-right", "margin-bottom", "margin-left",
+      "padding", "padding-top", "padding-right", "padding-bottom", "padding-left",
+      "width", "min-width", "max-width", "height", "min-height", "max-height",
+      "display", "float", "clear", "overflow", "opacity", "box-shadow", "list-style",
+    ];
+
+    Self {
+      attributes: attributes.iter().map(|v| String::from(*v)).collect(),
+      css_properties: css_properties.iter().map(|v| String::from(*v)).collect(),
+    }
+  }
+}
+
+impl AttributeWhitelist {
+  /// Drops attributes that are not whitelisted. `style` is reduced to the whitelisted declarations
+  /// and removed entirely when none remain. `id` gets a `u-` prefix, as on Wikidot, so that it cannot
+  /// take the ids of the table of contents, footnotes or collapsibles.
+  pub fn filter(&self, attributes: &[(String, String)]) -> Vec<(String, String)> {
+    let mut res = vec![];
+
+    for (key, value) in attributes {
+      let key = key.to_lowercase();
+      if !self.attributes.contains(&key) {
+        continue;
+      }
+
+      if key == "style" {
+        let style = self.filter_style(value);
+        if !style.is_empty() {
+          res.push((key, style));
+        }
+      } else if key == "id" {
+        res.push((key, format!("u-{}", value)));
+      } else {
+        res.push((key, value.clone()));
+      }
+    }
+
+    res
+  }
+
+  /// Keeps only `property: value` declarations whose property is whitelisted and whose value cannot
+  /// load resources or run scripts.
+  pub fn filter_style(&self, style: &str) -> String {
+    let mut declarations: Vec<String> = vec![];
+
+    for declaration in style.split(';') {
+      let Some((property, value)) = declaration.split_once(':') else {
+        continue;
+      };
+      let property = property.trim().to_lowercase();
+      let value = value.trim();
+
+      if !self.css_properties.contains(&property) || value.is_empty() || !is_safe_css_value(value) {
+        continue;
+      }
+
+      declarations.push(format!("{}: {}", property, value));
+    }
+
+    declarations.join("; ")
+  }
+}
+
+fn is_safe_css_value(value: &str) -> bool {
+  let lower = value.to_lowercase();
+  let forbidden_chars = ['\\', '<', '>', '{', '}', '"', '\'', '@'];
+  let forbidden_words = ["url(", "expression(", "javascript:", "vbscript:", "image-set(", "-moz-binding", "behavior"];
+
+  !lower.contains(forbidden_chars) && !forbidden_words.iter().any(|v| lower.contains(v))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn attrs(v: &[(&str, &str)]) -> Vec<(String, String)> {
+    v.iter().map(|(k, v)| (String::from(*k), String::from(*v))).collect()
+  }
+
+  #[test]
+  fn test_filter_attributes() {
+    let whitelist = AttributeWhitelist::default();
+    assert_eq!(
+      whitelist.filter(&attrs(&[("class", "box"), ("onclick", "alert(1)"), ("", "checked"), ("ID", "a")])),
+      attrs(&[("class", "box"), ("id", "u-a")]),
+    );
+    assert_eq!(whitelist.filter(&attrs(&[("id", "toc")])), attrs(&[("id", "u-toc")]));
+  }
+
+  #[test]
+  fn test_filter_style() {
+    let whitelist = AttributeWhitelist::default();
+    assert_eq!(
+      whitelist.filter_style("color: red; position: fixed; background: url(http://example.com/a.png); FONT-SIZE:120%"),
+      "color: red; font-size: 120%",
+    );
+    assert_eq!(whitelist.filter(&attrs(&[("style", "width: expression(alert(1))")])), vec![]);
+  }
+
+  #[test]
+  fn test_custom_whitelist() {
+    let whitelist = AttributeWhitelist { attributes: vec![String::from("style")], css_properties: vec![String::from("position")] };
+    assert_eq!(
+      whitelist.filter(&attrs(&[("class", "box"), ("style", "position: absolute; color: red")])),
+      attrs(&[("style", "position: absolute")]),
+    );
+  }
+}
@@ -0,0 +1,661 @@
+use crate::ast::{table_cell, Alignment, Float, HideLocation, ListKind, TreeElement, WikidotColor};
+use crate::error::RenderError;
+
+/// Serializes the AST back to Wikidot markup. Parsing the result gives the same AST again for
+/// anything the parser produced; text is escaped with `\` wherever it would otherwise be read as markup.
+/// `Iframe`, which the parser never produces, fails with `RenderError::UnsupportedElement`.
+pub fn render(ast: Vec<TreeElement>) -> Result<String, RenderError> {
+  let mut ast = ast.as_slice();
+  if is_implicit_footnote_block(ast) {
+    ast = &ast[..ast.len() - 1];
+  }
+
+  let mut w = Writer { res: String::new(), prefix: String::new(), depth: 0, is_definition_term: false };
+  w.blocks(ast)?;
+  Ok(w.res)
+}
+
+/// `inline::parse` appends `[[footnoteblock]]` to pages that have footnotes but no footnote block, so it need not be written.
+fn is_implicit_footnote_block(ast: &[TreeElement]) -> bool {
+  let mut footnotes = 0;
+  let mut footnote_blocks = 0;
+  let mut stack: Vec<&TreeElement> = ast.iter().collect();
+  while let Some(v) = stack.pop() {
+    match v {
+      TreeElement::Footnote { .. } => footnotes += 1,
+      TreeElement::FootnoteBlock { .. } => footnote_blocks += 1,
+      _ => {}
+    }
+    stack.extend(v.children());
+  }
+
+  matches!(ast.last(), Some(TreeElement::FootnoteBlock { title: None })) && footnote_blocks == 1 && footnotes > 0
+}
+
+/// `[[name key="value" bare]]`. `]` and `\` are escaped because the whole specifier is unescaped before it is split.
+fn element_tag(name: &str, attributes: &[(&str, &str)]) -> String {
+  let mut specifier = String::from(name);
+  for (key, value) in attributes {
+    specifier.push(' ');
+    if key.is_empty() {
+      specifier += value;
+    } else {
+      let quote = if value.contains('"') { '\'' } else { '"' };
+      specifier += &format!("{}={}{}{}", key, quote, value, quote);
+    }
+  }
+  format!("[[{}]]", escape_link(&specifier))
+}
+
+/// Escapes what would end a link or element early.
+fn escape_link(s: &str) -> String {
+  let mut res = String::new();
+  for c in s.chars() {
+    if c == '\\' || c == ']' {
+      res.push('\\');
+    }
+    res.push(c);
+  }
+  res
+}
+
+/// Whether `s` starts like the `red|` or `ff0000|` after `##`.
+fn starts_with_color(s: &[char]) -> bool {
+  let Some(bar) = s.iter().take(8).position(|c| *c == '|') else {
+    return false;
+  };
+  let word: String = s[..bar].iter().collect();
+  WikidotColor::from(&word).is_some() || (bar == 6 && word.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+struct Writer {
+  res: String,
+  prefix: String, // 引用の中では各行の先頭に"> "を置く
+  depth: usize,
+  is_definition_term: bool, // 定義語の中では最初の" : "が区切りになる
+}
+
+impl Writer {
+  fn newline(&mut self) {
+    self.res.push('\n');
+    self.res += &self.prefix;
+  }
+
+  fn is_line_start(&self) -> bool {
+    self.res.is_empty() || self.res.ends_with('\n')
+  }
+
+  fn write_text(&mut self, text: &str) {
+    let chars: Vec<char> = text.chars().collect();
+    for (at, &c) in chars.iter().enumerate() {
+      let next = chars.get(at + 1).copied();
+      let is_line_start = self.is_line_start();
+      let escape = match c {
+        '\\' | '\n' | '[' | '@' => true,
+        // 次の要素の記号と繋がらないよう, 末尾の文字も書き換える
+        '*' | '#' => is_line_start || next.is_none_or(|v| v == c),
+        '/' | '_' | '-' | '{' | '}' | '^' | ',' | '|' => next.is_none_or(|v| v == c),
+        '+' | ' ' => is_line_start,
+        // 行頭の"~~~~"は回り込みの解除になる
+        '~' if is_line_start && chars[at..].starts_with(&['~'; 4]) => true,
+        // 行頭の": "は後ろに" : "があると定義リストになり, 定義語の中の" : "は区切りになる
+        ':' => next == Some(' ') && (is_line_start || (self.is_definition_term && self.res.ends_with(' '))),
+        '>' => is_line_start || self.res.ends_with("||"),
+        '~' | '<' | '=' => self.res.ends_with("||"), // セルの書式
+        _ => false,
+      };
+      // ##の直後の"red|"は色の指定になる
+      let escape = escape || (at == 0 && self.res.ends_with("##") && starts_with_color(&chars));
+
+      if escape {
+        self.res.push('\\');
+      }
+      self.res.push(c);
+    }
+  }
+
+  /// Blocks separated by blank lines, or by line breaks inside a quote, where a blank line would end it.
+  fn blocks(&mut self, elements: &[TreeElement]) -> Result<(), RenderError> {
+    for (at, v) in elements.iter().enumerate() {
+      if at > 0 {
+        self.newline();
+        // 引用の中で続く引用は, 外側の引用だけの行で分ける
+        if self.prefix.is_empty() || matches!((&elements[at - 1], v), (TreeElement::QuoteBlock(_), TreeElement::QuoteBlock(_))) {
+          self.newline();
+        }
+      }
+      self.element(v)?;
+    }
+    Ok(())
+  }
+
+  fn inline(&mut self, elements: &[TreeElement]) -> Result<(), RenderError> {
+    for v in elements {
+      self.element(v)?;
+    }
+    Ok(())
+  }
+
+  /// `[[name ...]]`, the child blocks on their own lines, then `[[/name]]`.
+  fn block_element(&mut self, name: &str, attributes: &[(&str, &str)], children: &[TreeElement]) -> Result<(), RenderError> {
+    self.res += &element_tag(name, attributes);
+    if !children.is_empty() {
+      self.newline();
+      self.blocks(children)?;
+    }
+    self.newline();
+    self.res += &format!("[[/{}]]", name);
+    Ok(())
+  }
+
+  fn wrap(&mut self, open: &str, children: &[TreeElement], close: &str) -> Result<(), RenderError> {
+    self.res += open;
+    self.inline(children)?;
+    self.res += close;
+    Ok(())
+  }
+
+  fn list(&mut self, kind: ListKind, items: &[TreeElement], level: usize) -> Result<(), RenderError> {
+    for (at, item) in items.iter().enumerate() {
+      if at > 0 {
+        self.newline();
+      }
+      self.res += &" ".repeat(level - 1);
+      self.res += match kind {
+        ListKind::Unordered => "* ",
+        ListKind::Ordered => "# ",
+      };
+
+      let children = match item {
+        TreeElement::ListItem(children) => children.as_slice(),
+        v => std::slice::from_ref(v), // Lists only hold ListItems
+      };
+      for child in children {
+        if let TreeElement::List { kind, children } = child {
+          self.newline();
+          self.enter()?;
+          self.list(*kind, children, level + 1)?;
+          self.depth -= 1;
+        } else {
+          self.element(child)?;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn enter(&mut self) -> Result<(), RenderError> {
+    self.depth += 1;
+    if self.depth > super::MAX_RENDER_DEPTH {
+      return Err(RenderError::NestingTooDeep { limit: super::MAX_RENDER_DEPTH });
+    }
+    Ok(())
+  }
+
+  fn element(&mut self, v: &TreeElement) -> Result<(), RenderError> {
+    self.enter()?;
+
+    match v {
+      TreeElement::Paragraph(children) => self.inline(children)?,
+      TreeElement::Text(text) => self.write_text(text),
+      TreeElement::Bold(children) => self.wrap("**", children, "**")?,
+      TreeElement::Italics(children) => self.wrap("//", children, "//")?,
+      TreeElement::Underline(children) => self.wrap("__", children, "__")?,
+      TreeElement::Strikethrough(children) if children.is_empty() => self.res += "--@@@@--", // "----"だけの行は水平線になる
+      TreeElement::Strikethrough(children) => self.wrap("--", children, "--")?,
+      TreeElement::Monospaced(children) => self.wrap("{{", children, "}}")?,
+      TreeElement::Superscript(children) => self.wrap("^^", children, "^^")?,
+      TreeElement::Subscript(children) => self.wrap(",,", children, ",,")?,
+
+      TreeElement::Colored { red, green, blue, children } => {
+        let color = match WikidotColor::ALL.into_iter().find(|v| v.rgb() == (*red, *green, *blue)) {
+          Some(v) => String::from(v.name()),
+          None => format!("{:02x}{:02x}{:02x}", red, green, blue),
+        };
+        self.wrap(&format!("##{}|", color), children, "##")?;
+      }
+
+      TreeElement::Size { scale, children } => {
+        self.wrap(&element_tag("size", &[("", &scale.to_string())]), children, "[[/size]]")?;
+      }
+
+      TreeElement::Link { href, open_in_new_tab, name } => {
+        let star = if *open_in_new_tab { "*" } else { "" };
+        self.res += &format!("[{}{} {}]", star, escape_link(&href.0), escape_link(name));
+      }
+
+      TreeElement::InternalLink { href, open_in_new_tab, name } => {
+        let star = if *open_in_new_tab { "*" } else { "" };
+        if name.is_empty() {
+          self.res += &format!("[[[{}{}]]]", star, escape_link(href));
+        } else {
+          self.res += &format!("[[[{}{}|{}]]]", star, escape_link(href), escape_link(name));
+        }
+      }
+
+      TreeElement::Collapsible { show, hide, folded, hide_location, children } => {
+        let mut attributes = vec![];
+        if show != "+ show block" {
+          attributes.push(("show", show.as_str()));
+        }
+        if hide != "- hide block" {
+          attributes.push(("hide", hide.as_str()));
+        }
+        if !folded {
+          attributes.push(("folded", "no"));
+        }
+        if *hide_location != HideLocation::Top {
+          attributes.push(("hideLocation", hide_location.name()));
+        }
+        self.block_element("collapsible", &attributes, children)?;
+      }
+
+      TreeElement::Footnote { children, .. } => self.wrap("[[footnote]]", children, "[[/footnote]]")?, // idは書いた順に振り直される
+
+      TreeElement::FootnoteBlock { title } => {
+        match title {
+          Some(title) => self.res += &element_tag("footnoteblock", &[("title", title)]),
+          None => self.res += "[[footnoteblock]]",
+        }
+      }
+
+      TreeElement::QuoteBlock(children) => {
+        // 行頭に書いた外側の"> "を">> "に置き換える
+        let outer = std::mem::take(&mut self.prefix);
+        if let Some(len) = self.res.len().checked_sub(outer.len()) && self.res.ends_with(&outer) {
+          self.res.truncate(len);
+        }
+        self.prefix = ">".repeat(outer.matches('>').count() + 1) + " ";
+        self.res += &self.prefix;
+        self.blocks(children)?;
+        self.prefix = outer;
+      }
+
+      TreeElement::TableOfContents { float } => {
+        self.res += match float {
+          None => "[[toc]]",
+          Some(Float::Left) => "[[f<toc]]",
+          Some(Float::Right) => "[[f>toc]]",
+        };
+      }
+
+      TreeElement::Align { alignment, children } => self.block_element(alignment.element_name(), &[], children)?,
+
+      TreeElement::DefinitionList(items) => {
+        for (at, (term, definition)) in items.iter().enumerate() {
+          if at > 0 {
+            self.newline();
+          }
+          self.is_definition_term = true;
+          self.wrap(": ", term, " : ")?;
+          self.is_definition_term = false;
+          self.inline(definition)?;
+        }
+      }
+
+      TreeElement::HorizontalRule => self.res += "----",
+
+      TreeElement::ClearFloat(float) => {
+        self.res += match float {
+          None => "~~~~",
+          Some(Float::Left) => "~~~~<",
+          Some(Float::Right) => "~~~~>",
+        };
+      }
+
+      TreeElement::Image { source, alt, title, width, height, link, alignment, float, class, style } => {
+        let name = match (alignment, float) {
+          (None, _) => "image",
+          (Some(Alignment::Left), false) => "<image",
+          (Some(Alignment::Right), false) => ">image",
+          (Some(Alignment::Center), _) => "=image",
+          (Some(Alignment::Left), true) => "f<image",
+          (Some(Alignment::Right), true) => "f>image",
+        };
+        let width = width.map(|v| v.to_string());
+        let height = height.map(|v| v.to_string());
+        let mut attributes = vec![("", source.as_str())];
+        for (key, value) in [("link", link), ("alt", alt), ("title", title), ("width", &width), ("height", &height), ("class", class), ("style", style)] {
+          if let Some(value) = value {
+            attributes.push((key, value));
+          }
+        }
+        self.res += &element_tag(name, &attributes);
+      }
+
+      // 生のHTMLを読む[[html]]は実装していないので, 書いても読み戻せない
+      TreeElement::Iframe(_) => return Err(RenderError::UnsupportedElement { element: "Iframe" }),
+
+      TreeElement::CodeBlock { language, source } => {
+        match language {
+          Some(language) => self.res += &element_tag("code", &[("type", language)]),
+          None => self.res += "[[code]]",
+        }
+        // 前後の改行1つずつは読むときに取り除かれる
+        self.res += &format!("\n{}\n[[/code]]", source);
+      }
+
+      TreeElement::Tab { title, children } => self.block_element("tab", &[("", title)], children)?,
+      TreeElement::TabView(children) => self.block_element("tabview", &[], children)?,
+
+      TreeElement::Table(rows) => {
+        for (at, row) in rows.iter().enumerate() {
+          if at > 0 {
+            self.newline();
+          }
+          for cell in row {
+            self.res += &"||".repeat(cell.spanning.get() - 1);
+            self.res += match cell.style {
+              None => "||",
+              Some(table_cell::Style::Title) => "||~",
+              Some(table_cell::Style::LeftAligned) => "||<",
+              Some(table_cell::Style::RightAligned) => "||>",
+              Some(table_cell::Style::CenterAligned) => "||=",
+            };
+            self.inline(&cell.val)?;
+          }
+          self.res += "||";
+        }
+      }
+
+      TreeElement::Heading { level, children, anchor } => {
+        self.res += &"+".repeat(usize::from(*level));
+        if anchor.is_none() {
+          self.res.push('*');
+        }
+        self.res.push(' ');
+        self.inline(children)?;
+      }
+
+      TreeElement::List { kind, children } => self.list(*kind, children, 1)?,
+      TreeElement::ListItem(_) => self.list(ListKind::Unordered, std::slice::from_ref(v), 1)?, // outside of a List
+      TreeElement::NewLine => self.newline(),
+
+      TreeElement::HtmlElement { tag, property, children } => {
+        let attributes: Vec<(&str, &str)> = property.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        if tag == "div" {
+          self.block_element(tag, &attributes, children)?;
+        } else {
+          self.wrap(&element_tag(tag, &attributes), children, &format!("[[/{}]]", tag))?;
+        }
+      }
+    }
+
+    self.depth -= 1;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(ast: Vec<TreeElement>) -> String {
+    super::render(ast).unwrap()
+  }
+
+  fn round_trip(s: &str) -> String {
+    render(crate::parse_to_ast(String::from(s)).unwrap())
+  }
+
+  #[test]
+  fn test_inline_markup() {
+    for s in [
+      "**a** //b// __c__ --d-- {{e}} ^^f^^ ,,g,,",
+      "##red|a## ##0a0b0c|b## [[span class=\"x y\"]]c[[/span]]",
+      "[[size 1.5em]]a[[/size]] [[size smaller]]b[[/size]]",
+      "[https://example.com a link] [[[page]]] [[[page|name]]] [[[*page|new tab]]]",
+      "[[image a.png]] [[f<image /p/b.png link=\"page\" alt=\"a 'b'\" width=\"10\" class=\"x\"]] [[=image https://example.com/c.png title='\"c\"']]",
+      "a[[footnote]]note **b**[[/footnote]]",
+      "a\nb",
+      "a --@@@@-- b",
+    ] {
+      assert_eq!(round_trip(s), s);
+    }
+
+    assert_eq!(round_trip("##FF0000|a## [[[ page |  name ]]]"), "##red|a## [[[page|name]]]");
+  }
+
+  #[test]
+  fn test_blocks() {
+    for s in [
+      "+ Title\n\n++* Hidden\n\npara",
+      "> a\n>> b\n> c",
+      "> a\n\n> b",
+      "* a\n * b\n  # c\n* d\n\n# e",
+      "||~ a ||~ b ||\n||< c ||= d ||\n|||| e ||\n||",
+      "[[code type=\"rust\"]]\nfn main() {}\n[[/code]]",
+      "[[div class=\"box\"]]\n+ a\n\nb\n[[/div]]",
+      "[[collapsible show=\"+ Open\" folded=\"no\" hideLocation=\"both\"]]\na\n[[/collapsible]]",
+      "[[tabview]]\n[[tab One Two]]\na\n[[/tab]]\n\n[[tab Three]]\nb\n[[/tab]]\n[[/tabview]]",
+      "a[[footnote]]b[[/footnote]]\n\n[[footnoteblock title=\"Notes\"]]\n\nc",
+      "[[toc]]\n\n[[f<toc]]\n\n[[f>toc]]",
+      "a\n\n----\n\n~~~~\n\n~~~~<\n\n~~~~>",
+      ": a : **b**\n:  : c : d\n\n> x\n\n: e : \n: f \\: g : h",
+      "[[=]]\na\n\n[[==]]\n> b\n[[/==]]\n[[/=]]\n\n[[<]]\n[[/<]]\n\n[[>]]\n|| c ||\n[[/>]]",
+    ] {
+      assert_eq!(round_trip(s), s);
+    }
+
+    // 自動で足される[[footnoteblock]]は書かない
+    assert_eq!(round_trip("a[[footnote]]b[[/footnote]]"), "a[[footnote]]b[[/footnote]]");
+    assert_eq!(round_trip("------\n[[clearfloat]]"), "----\n\n~~~~");
+    assert_eq!(round_trip("--"), "--@@@@--");
+    assert_eq!(round_trip("##[[=]]\na\n[[/=]]"), "[[=]]\na\n[[/=]]");
+    assert_eq!(round_trip("[[/span]]\na\n[[/span]]\nb"), "a\nb");
+  }
+
+  #[test]
+  fn test_iframe() {
+    let ast = vec![TreeElement::Iframe(String::from("<p>raw</p>"))];
+    assert_eq!(super::render(ast), Err(RenderError::UnsupportedElement { element: "Iframe" }));
+  }
+
+  #[test]
+  fn test_escape() {
+    let text = |s: &str| vec![TreeElement::Paragraph(vec![TreeElement::Text(String::from(s))])];
+
+    assert_eq!(render(text("**a** [b c] @@ x\\y")), "\\**a\\** \\[b c] \\@\\@ x\\\\y");
+    assert_eq!(render(text("+ a\n* b\n> c")), "\\+ a\\\n\\* b\\\n\\> c");
+    assert_eq!(render(text("a-")), "a\\-");
+    assert_eq!(render(text("~~~~\n~~~~>\n----\na ~~~~")), "\\~~~~\\\n\\~~~~>\\\n\\-\\-\\--\\\na ~~~~");
+    assert_eq!(render(text(": t : d")), "\\: t : d");
+    assert_eq!(render(vec![TreeElement::Paragraph(vec![
+      TreeElement::Colored { red: 0, green: 0, blue: 0xFF, children: vec![] },
+      TreeElement::Text(String::from("red|a")),
+    ])]), "##blue|##\\red|a");
+  }
+
+  /// Writes random pages out of well-formed markup. Text is arbitrary, with every symbol escaped.
+  struct PageGenerator {
+    seed: u64,
+  }
+
+  impl PageGenerator {
+    fn next(&mut self, n: usize) -> usize {
+      self.seed = self.seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); // LCG
+      (self.seed >> 33) as usize % n
+    }
+
+    fn text(&mut self, s: &mut String) {
+      const CHARS: [&str; 39] = [
+        "a", "b", " ", "*", "/", "_", "-", "{", "}", "^", ",", "[", "]", "|", "#", ">", "+", "@", "\\", "~", "<", "=", "\"",
+        "\n", "red|", "ff0000|", "**", "//", "}}", "##", "||", "[[", "@@", "---", "----", "~~~~", "~~~~<", ": ", " : ",
+      ];
+      for _ in 0..1 + self.next(4) {
+        for c in CHARS[self.next(CHARS.len())].chars() {
+          if !c.is_ascii_alphanumeric() && c != ' ' {
+            s.push('\\');
+          }
+          s.push(c);
+        }
+      }
+    }
+
+    fn inline(&mut self, s: &mut String, depth: usize, newlines: bool, open: &mut Vec<&'static str>) {
+      for _ in 0..1 + self.next(4) {
+        let choice = if depth == 0 { 0 } else { self.next(12) };
+        match choice {
+          0..=2 => self.text(s),
+          3 => {
+            let mark = ["**", "//", "__", "--", "^^", ",,"][self.next(6)];
+            if open.contains(&mark) { // 内側の同じ記号は外側を閉じてしまう
+              continue;
+            }
+            *s += mark;
+            open.push(mark);
+            self.inline(s, depth - 1, newlines, open);
+            open.pop();
+            *s += mark;
+          }
+          4 => {
+            *s += "{{";
+            self.inline(s, depth - 1, newlines, open);
+            *s += "}}";
+          }
+          5 => {
+            *s += ["##red|", "##grey|", "##0a0b0c|"][self.next(3)];
+            self.inline(s, depth - 1, newlines, open);
+            *s += "##";
+          }
+          6 => {
+            let (open_tag, close_tag) = [("[[span class=\"x\"]]", "[[/span]]"), ("[[size 80%]]", "[[/size]]"), ("[[size X-Large]]", "[[/size]]")][self.next(3)];
+            *s += open_tag;
+            self.inline(s, depth - 1, newlines, open);
+            *s += close_tag;
+          }
+          7 => {
+            *s += "[[footnote]]";
+            self.inline(s, depth - 1, newlines, open);
+            *s += "[[/footnote]]";
+          }
+          8 => *s += ["[https://example.com a\\]b]", "[[[page]]]", "[[[*page|a|b]]]", "[[>image a.png alt=\"a]]b\"]]"][self.next(4)],
+          9 => *s += "@@**a//@@",
+          10 if newlines && !s.ends_with('\n') => *s += "\n", // 空行は段落を分ける
+          _ => self.text(s),
+        }
+      }
+    }
+
+    fn block(&mut self, s: &mut String, depth: usize) {
+      match self.next(if depth == 0 { 8 } else { 12 }) {
+        0 | 1 => self.inline(s, 3, true, &mut vec![]),
+        2 => {
+          *s += &"+".repeat(1 + self.next(6));
+          if self.next(2) == 0 {
+            s.push('*');
+          }
+          s.push(' ');
+          self.inline(s, 2, false, &mut vec![]);
+          if self.next(3) == 0 { // 同じ行でブロック要素を始める
+            self.container(s, depth.saturating_sub(1));
+          }
+        }
+        3 => {
+          for at in 0..1 + self.next(4) {
+            if at > 0 {
+              s.push('\n');
+            }
+            *s += &" ".repeat(self.next(3));
+            *s += ["* ", "# "][self.next(2)];
+            self.inline(s, 2, false, &mut vec![]);
+          }
+          if self.next(3) == 0 { // 同じ行でブロック要素を始める
+            self.container(s, depth.saturating_sub(1));
+          }
+        }
+        4 => {
+          for at in 0..1 + self.next(3) {
+            if at > 0 {
+              s.push('\n');
+            }
+            for _ in 0..1 + self.next(3) {
+              *s += ["||", "||~", "||<", "||>", "||=", "||||"][self.next(6)];
+              self.inline(s, 2, false, &mut vec![]);
+            }
+            *s += "||";
+          }
+        }
+        5 => {
+          for at in 0..1 + self.next(4) {
+            if at > 0 {
+              s.push('\n');
+            }
+            *s += &">".repeat(1 + self.next(3));
+            s.push(' ');
+            self.inline(s, 2, false, &mut vec![]);
+          }
+        }
+        6 => *s += ["[[toc]]", "[[f<toc]]", "[[f>toc]]", "----", "------", "~~~~", "~~~~<", "[[clearfloat]]"][self.next(8)],
+        7 => {
+          for at in 0..1 + self.next(3) {
+            if at > 0 {
+              s.push('\n');
+            }
+            *s += ": ";
+            self.inline(s, 2, false, &mut vec![]);
+            *s += " : ";
+            self.inline(s, 2, false, &mut vec![]);
+          }
+          if self.next(3) == 0 { // 同じ行でブロック要素を始める
+            self.container(s, depth.saturating_sub(1));
+          }
+        }
+        _ => self.container(s, depth - 1),
+      }
+    }
+
+    fn container(&mut self, s: &mut String, depth: usize) {
+      match self.next(5) {
+        0 => {
+          *s += "[[div class=\"box\"]]\n";
+          self.blocks(s, depth);
+          *s += "\n[[/div]]";
+        }
+        1 => {
+          *s += "[[collapsible show=\"open\" hideLocation=\"bottom\"]]\n";
+          self.blocks(s, depth);
+          *s += "\n[[/collapsible]]";
+        }
+        2 => {
+          let name = ["<", ">", "=", "=="][self.next(4)];
+          *s += &format!("[[{}]]\n", name);
+          self.blocks(s, depth);
+          *s += &format!("\n[[/{}]]", name);
+        }
+        3 => {
+          *s += "[[tab a]]\n";
+          self.blocks(s, depth);
+          *s += "\n[[/tab]]";
+        }
+        _ => {
+          *s += "[[tabview]]\n[[tab a b]]\n";
+          self.blocks(s, depth);
+          *s += "\n[[/tab]]\n[[tab c]]\n[[code]]\nx\n[[/code]]\n[[/tab]]\n[[/tabview]]";
+        }
+      }
+    }
+
+    fn blocks(&mut self, s: &mut String, depth: usize) {
+      for at in 0..1 + self.next(4) {
+        if at > 0 {
+          *s += "\n\n";
+        }
+        self.block(s, depth);
+      }
+    }
+  }
+
+  #[test]
+  fn test_parse_render_parse() {
+    let mut generator = PageGenerator { seed: 0x9e37_79b9_7f4a_7c15 };
+    for _ in 0..1000 {
+      let mut page = String::new();
+      generator.blocks(&mut page, 2);
+
+      let ast = crate::parse_to_ast(page.clone()).unwrap();
+      let wikitext = render(ast.clone());
+      assert_eq!(crate::parse_to_ast(wikitext.clone()).unwrap(), ast, "{:?} was written as {:?}", page, wikitext);
+    }
+  }
+}
@@ -1,21 +1,186 @@
-mod builder;
+pub(crate) mod builder;
 
-pub fn render(ast: Vec<crate::ast::TreeElement>) -> String {
+use crate::error::RenderError;
+
+/// Turns the source of a `[[code]]` block into the markup placed inside `<pre><code>`.
+pub trait Highlighter {
+  /// The returned string is written without escaping, so implementations must escape `source` themselves.
+  fn highlight(&self, language: Option<&str>, source: &str) -> String;
+}
+
+/// Escapes the source and adds no highlighting.
+pub struct PlainHighlighter;
+
+impl Highlighter for PlainHighlighter {
+  fn highlight(&self, _language: Option<&str>, source: &str) -> String {
+    builder::Builder::escape_chars(source)
+  }
+}
+
+/// How elements without content, like `<br>`, are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+  /// `<br />`
+  #[default]
+  Xhtml,
+  /// `<br>`, with `<!DOCTYPE html>` before a whole document.
+  Html5,
+}
+
+pub struct RenderOptions<'a> {
+  pub highlighter: &'a dyn Highlighter,
+  pub attribute_whitelist: super::whitelist::AttributeWhitelist,
+  pub link_resolver: &'a dyn super::resolver::LinkResolver,
+  pub image_source_resolver: &'a dyn super::resolver::ImageSourceResolver,
+  /// Writes only the contents of `<body>`, to be embedded in a page template that includes [`STYLESHEET`].
+  pub fragment: bool,
+  pub title: Option<String>,
+  /// `href`s of extra `<link rel="stylesheet">` in `<head>`.
+  pub stylesheets: Vec<String>,
+  /// `name` and `content` of extra `<meta>` in `<head>`.
+  pub meta: Vec<(String, String)>,
+  pub syntax: Syntax,
+  /// Indents block-level elements one per line, for reviewable snapshots. Inline markup and `<pre>` are unchanged.
+  pub pretty: bool,
+  /// Put before the generated collapsible and tab view ids, so fragments rendered separately can share a page.
+  pub id_prefix: String,
+}
+
+impl Default for RenderOptions<'_> {
+  fn default() -> Self {
+    Self {
+      highlighter: &PlainHighlighter,
+      attribute_whitelist: super::whitelist::AttributeWhitelist::default(),
+      link_resolver: &super::resolver::WikidotLinkResolver,
+      image_source_resolver: &super::resolver::WikidotImageSourceResolver { page: None },
+      fragment: false,
+      title: None,
+      stylesheets: vec![],
+      meta: vec![],
+      syntax: Syntax::default(),
+      pretty: false,
+      id_prefix: String::new(),
+    }
+  }
+}
+
+pub fn render(ast: Vec<crate::ast::TreeElement>) -> Result<String, RenderError> {
+  render_with_options(ast, &RenderOptions::default())
+}
+
+/// Rules for the CSS-only widgets (collapsible blocks, tab views) emitted by this renderer.
+/// Whole documents have it in `<head>`; pages embedding fragments must include it themselves.
+pub const STYLESHEET: &str = concat!(
+  ".collapsible-block-toggle{display:none}",
+  ".collapsible-block-toggle:checked~.collapsible-block-folded{display:none}",
+  ".collapsible-block-toggle:not(:checked)~.collapsible-block-unfolded{display:none}",
+  ".collapsible-block-link{cursor:pointer}",
+  ".tabview{display:flex;flex-wrap:wrap}",
+  ".tabview-radio{display:none}",
+  ".tabview-title{order:0;cursor:pointer;padding:0.2em 0.8em}",
+  ".tabview-radio:checked+.tabview-title{font-weight:bold}",
+  ".tabview-panel{order:1;width:100%;display:none}",
+  ".tabview-radio:checked+.tabview-title+.tabview-panel{display:block}",
+  "#toc{display:table;margin:0.5em 1em 0.5em 0;padding:0.5em 1em;border:1px solid #ccc}",
+  "#toc .title{font-weight:bold}",
+  ".image-container{display:block}",
+  ".image-container.alignleft{text-align:left}",
+  ".image-container.alignright{text-align:right}",
+  ".image-container.aligncenter{text-align:center}",
+  ".image-container.floatleft{float:left;margin:0 1em 1em 0}",
+  ".image-container.floatright{float:right;margin:0 0 1em 1em}",
+);
+
+struct Context<'o, 'a> {
+  options: &'o RenderOptions<'a>,
+  collapsible_count: usize,
+  tabview_count: usize,
+  footnotes: Option<Vec<(u32, Vec<crate::ast::TreeElement>)>>, // [[footnoteblock]]で書き出すため先に集めておく. 書いたらNone
+  toc: Vec<crate::toc::Entry>, // [[toc]]より後の見出しも載せる
+  depth: usize, // 呼び出し元のrender_elementsが開いている要素の数
+}
+
+/// Only the tags of `[[div]]` and `[[span]]`. A hand-built or deserialized AST could hold any other.
+pub(crate) fn is_allowed_tag(tag: &str) -> bool {
+  matches!(tag, "div" | "span")
+}
+
+pub(crate) fn collect_footnotes(ast: &[crate::ast::TreeElement]) -> Vec<(u32, Vec<crate::ast::TreeElement>)> {
   use crate::ast::TreeElement;
 
-  let mut res = builder::Builder::new();
+  let mut res = vec![];
+  let mut stack: Vec<&TreeElement> = ast.iter().rev().collect();
+  while let Some(v) = stack.pop() {
+    if let TreeElement::Footnote { id, children } = v {
+      res.push((*id, children.clone()));
+    }
+    stack.extend(v.children().into_iter().rev());
+  }
+
+  res
+}
+
+pub fn render_with_options(ast: Vec<crate::ast::TreeElement>, options: &RenderOptions) -> Result<String, RenderError> {
+  let mut res = builder::Builder::new(options.syntax).pretty(options.pretty);
+
+  if !options.fragment {
+    if options.syntax == Syntax::Html5 {
+      res.write_raw("<!DOCTYPE html>");
+    }
+    res.open(String::from("html"), vec![]);
+    res.open(String::from("head"), vec![]);
+    res.insert(String::from("meta"), vec![("charset", "UTF-8")]);
+    res.insert(String::from("meta"), vec![("name", "viewport"), ("content", "width=device-width, initial-scale=1")]);
+    for (name, content) in &options.meta {
+      res.insert(String::from("meta"), vec![("name", name), ("content", content)]);
+    }
+    if let Some(title) = &options.title {
+      res.open(String::from("title"), vec![]);
+      res.write(title);
+      res.close(); // </title>
+    }
+    res.open(String::from("style"), vec![]);
+    res.write_raw(STYLESHEET);
+    res.close(); // </style>
+    for href in &options.stylesheets { // 後に書いたものが優先される
+      res.insert(String::from("link"), vec![("rel", "stylesheet"), ("href", href)]);
+    }
+    res.close(); // </head>
+    res.open(String::from("body"), vec![]);
+  }
+
+  let mut ctx = Context {
+    options,
+    collapsible_count: 0,
+    tabview_count: 0,
+    footnotes: Some(collect_footnotes(&ast)),
+    toc: crate::toc::outline(&ast),
+    depth: 0,
+  };
+  render_elements(&mut res, ast, &mut ctx)?;
 
-  res.open(String::from("html"), vec![]);
-  res.open(String::from("head"), vec![]);
-  res.insert(String::from("meta"), vec![("charset", "UTF-8")]);
-  res.insert(String::from("meta"), vec![("name", "viewport"), ("content", "width=device-width, initial-scale=1")]);
-  res.close(); // </head>
-  res.open(String::from("body"), vec![]);
+  if !options.fragment {
+    res.close(); // </body>
+    res.close(); // </html>
+  }
+
+  Ok(res.into())
+}
+
+fn render_elements(res: &mut builder::Builder, ast: Vec<crate::ast::TreeElement>, ctx: &mut Context) -> Result<(), RenderError> {
+  use crate::ast::{HideLocation, TreeElement};
+  use crate::ast::table_cell::Style;
 
+  let options = ctx.options;
+  let base_depth = ctx.depth;
   let mut iters = vec![ast.into_iter()];
 
   while !iters.is_empty() {
-    if let Some(v) = iters.last_mut().unwrap().next() {
+    if base_depth + iters.len() > super::MAX_RENDER_DEPTH {
+      return Err(RenderError::NestingTooDeep { limit: super::MAX_RENDER_DEPTH });
+    }
+
+    if let Some(v) = iters.last_mut().and_then(Iterator::next) {
       match v {
         TreeElement::Paragraph(children) => {
           res.open(String::from("p"), vec![]);
@@ -67,12 +232,16 @@ pub fn render(ast: Vec<crate::ast::TreeElement>) -> String {
         }
 
         TreeElement::Size { scale, children } => {
-          res.open(String::from("span"), vec![("style", &format!("font-size: {}", scale.as_string()))]);
+          res.open(String::from("span"), vec![("style", &format!("font-size: {}", scale))]);
           iters.push(children.into_iter());
         }
 
         TreeElement::Link { href, open_in_new_tab, name } => {
-          let mut attrs = vec![("href", &href.0 as &str)];
+          let Some(href) = super::resolver::external_link(&href.0) else {
+            res.write(&name); // 危ないスキームのリンクは名前だけ書く
+            continue;
+          };
+          let mut attrs = vec![("href", href.as_str())];
           if open_in_new_tab {
             attrs.push(("target", "_blank"));
             attrs.push(("rel", "noopener noreferrer"));
@@ -83,16 +252,97 @@ pub fn render(ast: Vec<crate::ast::TreeElement>) -> String {
         }
 
         TreeElement::InternalLink { href, open_in_new_tab, name } => {
-          todo!();
+          let url = options.link_resolver.resolve(&href);
+          let mut attrs = vec![("href", url.as_str())];
+          if !options.link_resolver.exists(&href) {
+            attrs.push(("class", "newpage"));
+          }
+          if open_in_new_tab {
+            attrs.push(("target", "_blank"));
+          }
+          res.open(String::from("a"), attrs);
+          if name.is_empty() {
+            res.write(&options.link_resolver.link_text(&href));
+          } else {
+            res.write(&name);
+          }
+          res.close()
         }
 
-        TreeElement::Collapsible(children) => {
-          res.open(String::from("details"), vec![]);
-          iters.push(children.into_iter());
+        TreeElement::Collapsible { show, hide, folded, hide_location, children } => {
+          // チェックボックスとlabelで開閉する. JavaScriptは使わない.
+          ctx.collapsible_count += 1;
+          let id = format!("{}collapsible-{}", options.id_prefix, ctx.collapsible_count);
+          let hide_link = |res: &mut builder::Builder| {
+            res.open(String::from("div"), vec![("class", "collapsible-block-unfolded-link")]);
+            res.open(String::from("label"), vec![("for", &id), ("class", "collapsible-block-link")]);
+            res.write(&hide);
+            res.close(); // </label>
+            res.close(); // </div>
+          };
+
+          res.open(String::from("div"), vec![("class", "collapsible-block")]);
+          let mut toggle_attrs = vec![("type", "checkbox"), ("class", "collapsible-block-toggle"), ("id", id.as_str())];
+          if !folded {
+            toggle_attrs.push(("checked", "checked"));
+          }
+          res.insert(String::from("input"), toggle_attrs);
+
+          res.open(String::from("div"), vec![("class", "collapsible-block-folded")]);
+          res.open(String::from("label"), vec![("for", &id), ("class", "collapsible-block-link")]);
+          res.write(&show);
+          res.close(); // </label>
+          res.close(); // </div>
+
+          res.open(String::from("div"), vec![("class", "collapsible-block-unfolded")]);
+          if matches!(hide_location, HideLocation::Top | HideLocation::Both) {
+            hide_link(res);
+          }
+          res.open(String::from("div"), vec![("class", "collapsible-block-content")]);
+          ctx.depth = base_depth + iters.len();
+          render_elements(res, children, ctx)?;
+          res.close(); // </div>
+          if matches!(hide_location, HideLocation::Bottom | HideLocation::Both) {
+            hide_link(res);
+          }
+          res.close(); // </div>
+          res.close(); // </div>
+        }
+
+        TreeElement::Footnote{id, ..} => {
+          let href = format!("#footnote-{}", id);
+          let ref_id = format!("footnoteref-{}", id);
+          res.open(String::from("sup"), vec![("class", "footnoteref")]);
+          res.open(String::from("a"), vec![("id", &ref_id), ("href", &href), ("class", "footnoteref")]);
+          res.write(&id.to_string());
+          res.close(); // </a>
+          res.close(); // </sup>
         }
 
-        TreeElement::Footnote{id, children} => {
-          todo!();
+        TreeElement::FootnoteBlock{title} => {
+          // 2つ目からは書かない. 同じidが並んでしまう
+          if let Some(footnotes) = ctx.footnotes.take() {
+            res.open(String::from("div"), vec![("class", "footnotes-footer")]);
+            let title = title.unwrap_or(String::from("Footnotes"));
+            if !title.is_empty() {
+              res.open(String::from("div"), vec![("class", "title")]);
+              res.write(&title);
+              res.close();
+            }
+            for (id, children) in footnotes {
+              let footnote_id = format!("footnote-{}", id);
+              let href = format!("#footnoteref-{}", id);
+              res.open(String::from("div"), vec![("class", "footnote-footer"), ("id", &footnote_id)]);
+              res.open(String::from("a"), vec![("href", &href)]);
+              res.write(&id.to_string());
+              res.close(); // </a>
+              res.write(". ");
+              ctx.depth = base_depth + iters.len();
+              render_elements(res, children, ctx)?;
+              res.close(); // </div>
+            }
+            res.close(); // </div>
+          }
         }
 
         TreeElement::QuoteBlock(children) => {
@@ -105,31 +355,213 @@ pub fn render(ast: Vec<crate::ast::TreeElement>) -> String {
           res.close();
         }
 
-        TreeElement::Tab{title, children} => {
+        TreeElement::CodeBlock { language, source } => {
+          let class = language.as_ref().map(|v| format!("language-{}", v));
+          res.open(String::from("div"), vec![("class", "code")]);
+          res.open(String::from("pre"), vec![]);
+          res.open(String::from("code"), class.iter().map(|v| ("class", v.as_str())).collect());
+          res.write_raw(&options.highlighter.highlight(language.as_deref(), &source));
+          res.close(); // </code>
+          res.close(); // </pre>
+          res.close(); // </div>
+        }
 
+        TreeElement::Tab { children, .. } => { // outside of a TabView
+          res.open(String::from("div"), vec![("class", "tabview-panel")]);
+          iters.push(children.into_iter());
         }
 
-        TreeElement::TabView(_) => {
-          todo!()
+        TreeElement::TabView(tabs) => {
+          // タブごとに radio, label, パネルの順で並べ, CSSの:checkedで切り替える.
+          ctx.tabview_count += 1;
+          let tabview_id = format!("{}tabview-{}", options.id_prefix, ctx.tabview_count);
+
+          res.open(String::from("div"), vec![("class", "tabview"), ("id", &tabview_id)]);
+          let tabs = tabs.into_iter().filter_map(|v| match v {
+            TreeElement::Tab { title, children } => Some((title, children)),
+            _ => None, // TabView only holds Tabs
+          });
+          for (at, (title, children)) in tabs.enumerate() {
+            let radio_id = format!("{}-tab-{}", tabview_id, at + 1);
+            let panel_id = format!("{}-panel-{}", tabview_id, at + 1);
+
+            let mut radio_attrs = vec![("type", "radio"), ("name", tabview_id.as_str()), ("id", radio_id.as_str()), ("class", "tabview-radio")];
+            if at == 0 {
+              radio_attrs.push(("checked", "checked"));
+            }
+            res.insert(String::from("input"), radio_attrs);
+
+            res.open(String::from("label"), vec![("for", &radio_id), ("class", "tabview-title")]);
+            res.write(&title);
+            res.close(); // </label>
+
+            res.open(String::from("div"), vec![("class", "tabview-panel"), ("id", &panel_id)]);
+            ctx.depth = base_depth + iters.len();
+            render_elements(res, children, ctx)?;
+            res.close(); // </div>
+          }
+          res.close(); // </div>
         }
 
         TreeElement::Table(rows) => {
-          res.open(String::from("table"), vec![]);
+          res.open(String::from("table"), vec![("class", "wiki-content-table")]);
           for row in rows {
             res.open(String::from("tr"), vec![]);
             for cell in row {
-              todo!();
+              let tag = if cell.style == Some(Style::Title) { "th" } else { "td" };
+              let colspan = cell.spanning.to_string();
+
+              let mut attrs = vec![];
+              match cell.style {
+                Some(Style::LeftAligned) => attrs.push(("style", "text-align: left")),
+                Some(Style::RightAligned) => attrs.push(("style", "text-align: right")),
+                Some(Style::CenterAligned) => attrs.push(("style", "text-align: center")),
+                Some(Style::Title) | None => {}
+              }
+              if cell.spanning.get() > 1 {
+                attrs.push(("colspan", &colspan));
+              }
+
+              res.open(String::from(tag), attrs);
+              ctx.depth = base_depth + iters.len();
+              render_elements(res, cell.val, ctx)?;
+              res.close();
+            }
+            res.close(); // </tr>
+          }
+          res.close(); // </table>
+        }
+
+        TreeElement::DefinitionList(items) => {
+          res.open(String::from("dl"), vec![]);
+          for (term, definition) in items {
+            for (tag, children) in [("dt", term), ("dd", definition)] {
+              res.open(String::from(tag), vec![]);
+              ctx.depth = base_depth + iters.len();
+              render_elements(res, children, ctx)?;
+              res.close();
             }
-            res.close();
           }
+          res.close(); // </dl>
+        }
+
+        TreeElement::Heading { level, children, anchor } => {
+          let mut attrs = vec![];
+          if let Some(anchor) = &anchor {
+            attrs.push(("id", anchor.as_str()));
+          }
+          res.open(format!("h{}", level.clamp(1, 6)), attrs);
+          iters.push(children.into_iter());
+        }
+
+        TreeElement::TableOfContents { float } => {
+          let mut attrs = vec![("id", "toc")];
+          match float {
+            Some(crate::ast::Float::Left) => attrs.push(("style", "float: left")),
+            Some(crate::ast::Float::Right) => attrs.push(("style", "float: right")),
+            None => {}
+          }
+          res.open(String::from("div"), attrs);
+          res.open(String::from("div"), vec![("class", "title")]);
+          res.write("Table of Contents");
+          res.close(); // </div>
+          res.open(String::from("div"), vec![("id", "toc-list")]);
+
+          let mut stack: Vec<(usize, &crate::toc::Entry)> = ctx.toc.iter().rev().map(|v| (0, v)).collect();
+          while let Some((depth, entry)) = stack.pop() {
+            res.open(String::from("div"), vec![("style", &format!("margin-left: {}em", depth + 2))]);
+            res.open(String::from("a"), vec![("href", &format!("#{}", entry.anchor))]);
+            res.write(&entry.text());
+            res.close(); // </a>
+            res.close(); // </div>
+            stack.extend(entry.children.iter().rev().map(|v| (depth + 1, v)));
+          }
+
+          res.close(); // </div>
+          res.close(); // </div>
+        }
+
+        TreeElement::Align { alignment, children } => {
+          res.open(String::from("div"), vec![("style", &format!("text-align: {}", alignment.css_value()))]);
+          iters.push(children.into_iter());
+        }
+
+        TreeElement::HorizontalRule => {
+          res.insert(String::from("hr"), vec![]);
+        }
+
+        TreeElement::ClearFloat(float) => {
+          let style = match float {
+            Some(crate::ast::Float::Left) => "clear: left; height: 0px; font-size: 1px",
+            Some(crate::ast::Float::Right) => "clear: right; height: 0px; font-size: 1px",
+            None => "clear: both; height: 0px; font-size: 1px",
+          };
+          res.open(String::from("div"), vec![("style", style)]);
           res.close();
         }
 
+        TreeElement::Image { source, alt, title, width, height, link, alignment, float, class, style } => {
+          use crate::ast::Alignment;
+
+          let container = alignment.map(|v| match (v, float) {
+            (Alignment::Left, true) => "image-container floatleft",
+            (Alignment::Right, true) => "image-container floatright",
+            (Alignment::Left, false) => "image-container alignleft",
+            (Alignment::Right, false) => "image-container alignright",
+            (Alignment::Center, _) => "image-container aligncenter",
+          });
+          if let Some(container) = container { // 段落の中に置かれるのでdivにはできない
+            res.open(String::from("span"), vec![("class", container)]);
+          }
+          let href = link.and_then(|v| super::resolver::image_link(&v, options.link_resolver));
+          if let Some(href) = &href {
+            res.open(String::from("a"), vec![("href", href)]);
+          }
+
+          let src = options.image_source_resolver.resolve(&source);
+          let alt = alt.unwrap_or_else(|| String::from(source.rsplit('/').next().unwrap_or_default())); // Wikidotと同じくファイル名
+          let class = class.map_or(String::from("image"), |v| format!("image {}", v));
+          let width = width.map(|v| v.to_string());
+          let height = height.map(|v| v.to_string());
+          let style = style.map(|v| options.attribute_whitelist.filter_style(&v)).filter(|v| !v.is_empty());
+          let mut attrs = vec![("src", src.as_str()), ("alt", alt.as_str()), ("class", class.as_str())];
+          for (key, value) in [("title", &title), ("width", &width), ("height", &height), ("style", &style)] {
+            if let Some(value) = value {
+              attrs.push((key, value.as_str()));
+            }
+          }
+          res.insert(String::from("img"), attrs);
+
+          if href.is_some() {
+            res.close(); // </a>
+          }
+          if container.is_some() {
+            res.close(); // </span>
+          }
+        }
+
+        TreeElement::List { kind, children } => {
+          res.open(String::from(match kind {
+            crate::ast::ListKind::Unordered => "ul",
+            crate::ast::ListKind::Ordered => "ol",
+          }), vec![]);
+          iters.push(children.into_iter());
+        }
+
+        TreeElement::ListItem(children) => {
+          res.open(String::from("li"), vec![]);
+          iters.push(children.into_iter());
+        }
+
         TreeElement::NewLine => {
           res.insert(String::from("br"), vec![]);
         }
 
         TreeElement::HtmlElement { tag, property, children } => {
+          if !is_allowed_tag(&tag) {
+            return Err(RenderError::InvalidTagName { tag });
+          }
+          let property = options.attribute_whitelist.filter(&property);
           let attrs: Vec<(&str, &str)> = property.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
           res.open(tag, attrs);
           iters.push(children.into_iter());
@@ -141,8 +573,303 @@ pub fn render(ast: Vec<crate::ast::TreeElement>) -> String {
     }
   }
 
-  res.close(); // </body>
-  res.close(); // </html>
+  Ok(())
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ast::TreeElement;
+
+  fn render(ast: Vec<TreeElement>) -> String {
+    super::render(ast).unwrap()
+  }
+
+  fn render_with_options(ast: Vec<TreeElement>, options: &RenderOptions) -> String {
+    super::render_with_options(ast, options).unwrap()
+  }
+
+  struct UpperHighlighter;
+
+  impl Highlighter for UpperHighlighter {
+    fn highlight(&self, language: Option<&str>, source: &str) -> String {
+      format!("<span class=\"{}\">{}</span>", language.unwrap_or("none"), source.to_uppercase())
+    }
+  }
+
+  #[test]
+  fn test_html_element_whitelist() {
+    let html = render(vec![TreeElement::HtmlElement {
+      tag: String::from("span"),
+      property: vec![
+        (String::from("style"), String::from("color: red; position: fixed")),
+        (String::from("onmouseover"), String::from("alert(1)")),
+      ],
+      children: vec![TreeElement::Text(String::from("x"))],
+    }]);
+    assert!(html.contains(r#"<span style="color: red">x</span>"#));
+
+    for tag in ["script", "iframe", "style", "DIV"] {
+      let ast = vec![TreeElement::HtmlElement { tag: String::from(tag), property: vec![], children: vec![] }];
+      assert_eq!(super::render(ast), Err(RenderError::InvalidTagName { tag: String::from(tag) }));
+    }
+  }
+
+  #[test]
+  fn test_footnotes() {
+    let html = render(vec![
+      TreeElement::Paragraph(vec![
+        TreeElement::Text(String::from("a")),
+        TreeElement::Footnote { id: 1, children: vec![TreeElement::Bold(vec![TreeElement::Text(String::from("note"))])] },
+      ]),
+      TreeElement::FootnoteBlock { title: Some(String::from("Notes")) },
+    ]);
+    assert!(html.contains(r##"<p>a<sup class="footnoteref"><a id="footnoteref-1" href="#footnote-1" class="footnoteref">1</a></sup></p>"##));
+    assert!(html.contains(r##"<div class="footnotes-footer"><div class="title">Notes</div><div class="footnote-footer" id="footnote-1"><a href="#footnoteref-1">1</a>. <strong>note</strong></div></div>"##));
+
+    let html = render(crate::parse_to_ast(String::from("a[[footnote]]b[[/footnote]]\n\n[[footnoteblock]]\n\n[[footnoteblock]]")).unwrap());
+    assert_eq!(html.matches(r#"id="footnote-1""#).count(), 1);
+    assert_eq!(html.matches("footnotes-footer").count(), 1);
+  }
+
+  #[test]
+  fn test_collapsible() {
+    let html = render(vec![TreeElement::Collapsible {
+      show: String::from("+ Open"),
+      hide: String::from("- Close"),
+      folded: false,
+      hide_location: crate::ast::HideLocation::Both,
+      children: vec![TreeElement::Paragraph(vec![TreeElement::Text(String::from("x"))])],
+    }]);
+    assert!(html.contains(concat!(
+      r#"<div class="collapsible-block"><input type="checkbox" class="collapsible-block-toggle" id="collapsible-1" checked="checked" />"#,
+      r#"<div class="collapsible-block-folded"><label for="collapsible-1" class="collapsible-block-link">+ Open</label></div>"#,
+      r#"<div class="collapsible-block-unfolded">"#,
+      r#"<div class="collapsible-block-unfolded-link"><label for="collapsible-1" class="collapsible-block-link">- Close</label></div>"#,
+      r#"<div class="collapsible-block-content"><p>x</p></div>"#,
+      r#"<div class="collapsible-block-unfolded-link"><label for="collapsible-1" class="collapsible-block-link">- Close</label></div>"#,
+      r#"</div></div>"#,
+    )));
+  }
+
+  #[test]
+  fn test_tabview() {
+    let tab = |title: &str, text: &str| TreeElement::Tab {
+      title: String::from(title),
+      children: vec![TreeElement::Paragraph(vec![TreeElement::Text(String::from(text))])],
+    };
+    let html = render(vec![TreeElement::TabView(vec![tab("A", "a"), tab("B", "b")]), TreeElement::TabView(vec![tab("C", "c")])]);
+    assert!(html.contains(concat!(
+      r#"<div class="tabview" id="tabview-1">"#,
+      r#"<input type="radio" name="tabview-1" id="tabview-1-tab-1" class="tabview-radio" checked="checked" />"#,
+      r#"<label for="tabview-1-tab-1" class="tabview-title">A</label>"#,
+      r#"<div class="tabview-panel" id="tabview-1-panel-1"><p>a</p></div>"#,
+      r#"<input type="radio" name="tabview-1" id="tabview-1-tab-2" class="tabview-radio" />"#,
+      r#"<label for="tabview-1-tab-2" class="tabview-title">B</label>"#,
+      r#"<div class="tabview-panel" id="tabview-1-panel-2"><p>b</p></div>"#,
+      r#"</div>"#,
+    )));
+    assert!(html.contains(r#"<div class="tabview" id="tabview-2"><input type="radio" name="tabview-2" id="tabview-2-tab-1""#));
+  }
+
+  #[test]
+  fn test_table() {
+    use crate::ast::table_cell::{Cell, Style};
+    let cell = |s: &str, style: Option<Style>, spanning: usize| Cell {
+      val: vec![TreeElement::Text(String::from(s))],
+      style,
+      spanning: std::num::NonZeroUsize::new(spanning).unwrap(),
+    };
+    let html = render(vec![TreeElement::Table(vec![
+      vec![cell("a", Some(Style::Title), 2)],
+      vec![cell("b", Some(Style::LeftAligned), 1), cell("c", Some(Style::RightAligned), 1)],
+      vec![cell("d", Some(Style::CenterAligned), 1), cell("e", None, 1)],
+    ])]);
+    assert!(html.contains(concat!(
+      r#"<table class="wiki-content-table">"#,
+      r#"<tr><th colspan="2">a</th></tr>"#,
+      r#"<tr><td style="text-align: left">b</td><td style="text-align: right">c</td></tr>"#,
+      r#"<tr><td style="text-align: center">d</td><td>e</td></tr>"#,
+      r#"</table>"#,
+    )));
+  }
+
+  struct MissingPages;
+
+  impl crate::renderer::resolver::LinkResolver for MissingPages {
+    fn resolve(&self, page: &str) -> String {
+      format!("https://example.com/{}", page)
+    }
+
+    fn exists(&self, page: &str) -> bool {
+      page != "missing"
+    }
+
+    fn link_text(&self, page: &str) -> String {
+      format!("Title of {}", page)
+    }
+  }
+
+  #[test]
+  fn test_internal_link() {
+    let link = |href: &str, name: &str| TreeElement::InternalLink { href: String::from(href), open_in_new_tab: false, name: String::from(name) };
+
+    let html = render(vec![link("Some Page#toc1", "")]);
+    assert!(html.contains(r##"<a href="/some-page#toc1">Some Page</a>"##));
+
+    let options = RenderOptions { link_resolver: &MissingPages, ..Default::default() };
+    let html = render_with_options(vec![link("missing", "go"), link("found", "")], &options);
+    assert!(html.contains(r#"<a href="https://example.com/missing" class="newpage">go</a>"#));
+    assert!(html.contains(r#"<a href="https://example.com/found">Title of found</a>"#));
+  }
+
+  #[test]
+  fn test_external_link() {
+    let link = |href: &str| TreeElement::Link { href: crate::ast::Url(String::from(href)), open_in_new_tab: false, name: String::from("click") };
+
+    let html = render(vec![link("https://example.com/a")]);
+    assert!(html.contains(r#"<a href="https://example.com/a">click</a>"#));
+    for href in ["javascript:alert(1)", "data:text/html,<script>alert(1)</script>", " JaVaScRiPt:alert(1)"] {
+      let html = render(vec![link(href)]);
+      assert!(!html.contains("<a") && html.contains("click"), "{}", html);
+    }
+  }
+
+  #[test]
+  fn test_code_block_plain() {
+    let html = render(vec![TreeElement::CodeBlock { language: None, source: String::from("a < b") }]);
+    assert!(html.contains(r#"<div class="code"><pre><code>a &lt; b</code></pre></div>"#));
+  }
+
+  #[test]
+  fn test_code_block_highlighter() {
+    let options = RenderOptions { highlighter: &UpperHighlighter, ..Default::default() };
+    let html = render_with_options(vec![TreeElement::CodeBlock { language: Some(String::from("rust")), source: String::from("fn") }], &options);
+    assert!(html.contains(r#"<pre><code class="language-rust"><span class="rust">FN</span></code></pre>"#));
+  }
+
+  #[test]
+  fn test_fragment() {
+    let options = RenderOptions { fragment: true, ..Default::default() };
+    let html = render_with_options(vec![TreeElement::Paragraph(vec![TreeElement::Text(String::from("a")), TreeElement::NewLine])], &options);
+    assert_eq!(html, "<p>a<br /></p>");
+
+    let options = RenderOptions { fragment: true, syntax: Syntax::Html5, ..Default::default() };
+    let html = render_with_options(vec![TreeElement::NewLine], &options);
+    assert_eq!(html, "<br>");
+  }
+
+  #[test]
+  fn test_id_prefix() {
+    let ast = || crate::parse_to_ast(String::from("[[collapsible]]\nx\n[[/collapsible]]\n[[tabview]]\n[[tab A]]\na\n[[/tab]]\n[[/tabview]]")).unwrap();
+    let first = render_with_options(ast(), &RenderOptions { fragment: true, id_prefix: String::from("a-"), ..Default::default() });
+    let second = render_with_options(ast(), &RenderOptions { fragment: true, id_prefix: String::from("b-"), ..Default::default() });
+    assert!(first.contains(r#"id="a-collapsible-1""#) && first.contains(r#"for="a-collapsible-1""#));
+    assert!(second.contains(r#"id="b-collapsible-1""#) && second.contains(r#"for="b-collapsible-1""#));
+    assert!(first.contains(r#"<div class="tabview" id="a-tabview-1"><input type="radio" name="a-tabview-1" id="a-tabview-1-tab-1""#));
+    assert!(second.contains(r#"<div class="tabview" id="b-tabview-1"><input type="radio" name="b-tabview-1" id="b-tabview-1-tab-1""#));
+    assert!(second.contains(r#"<label for="b-tabview-1-tab-1" class="tabview-title">A</label><div class="tabview-panel" id="b-tabview-1-panel-1">"#));
+    let both = first + &second;
+    assert!(!both.contains(r#""collapsible-1""#) && !both.contains(r#""tabview-1"#));
+  }
+
+  #[test]
+  fn test_document_head() {
+    let options = RenderOptions {
+      title: Some(String::from("A & B")),
+      stylesheets: vec![String::from("/site.css")],
+      meta: vec![(String::from("description"), String::from("x"))],
+      syntax: Syntax::Html5,
+      ..Default::default()
+    };
+    let html = render_with_options(vec![], &options);
+    assert!(html.starts_with(r#"<!DOCTYPE html><html><head><meta charset="UTF-8">"#));
+    assert!(html.contains(r#"<meta name="description" content="x"><title>A &amp; B</title><style>"#));
+    assert!(html.ends_with(r#"</style><link rel="stylesheet" href="/site.css"></head><body></body></html>"#));
+  }
+
+  #[test]
+  fn test_pretty() {
+    let options = RenderOptions { fragment: true, pretty: true, ..Default::default() };
+    let ast = crate::parse_to_ast(String::from("[[div]]\na **b** c\n\n* d\n * e\n[[/div]]\n\n[[code]]\nx\n  y\n[[/code]]")).unwrap();
+    assert_eq!(render_with_options(ast, &options), concat!(
+      "<div>\n",
+      "  <p>a <strong>b</strong> c</p>\n",
+      "  <ul>\n",
+      "    <li>d\n",
+      "      <ul>\n",
+      "        <li>e</li>\n",
+      "      </ul>\n",
+      "    </li>\n",
+      "  </ul>\n",
+      "</div>\n",
+      "<div class=\"code\">\n",
+      "  <pre><code>x\n  y</code></pre>\n",
+      "</div>",
+    ));
+
+    let options = RenderOptions { pretty: true, ..Default::default() };
+    assert!(render_with_options(vec![], &options).starts_with("<html>\n  <head>\n    <meta charset=\"UTF-8\" />\n"));
+  }
+
+  #[test]
+  fn test_table_of_contents() {
+    let options = RenderOptions { fragment: true, ..Default::default() };
+    let ast = crate::parse_to_ast(String::from("[[f>toc]]\n+ A\n++ B\n+* C\n+ D")).unwrap();
+    let html = render_with_options(ast, &options);
+    assert!(html.starts_with(concat!(
+      r#"<div id="toc" style="float: right"><div class="title">Table of Contents</div><div id="toc-list">"#,
+      r##"<div style="margin-left: 2em"><a href="#toc0">A</a></div>"##,
+      r##"<div style="margin-left: 3em"><a href="#toc1">B</a></div>"##,
+      r##"<div style="margin-left: 2em"><a href="#toc2">D</a></div>"##,
+      r#"</div></div><h1 id="toc0">A</h1>"#,
+    )), "{}", html);
+  }
+
+  #[test]
+  fn test_definition_list() {
+    let options = RenderOptions { fragment: true, ..Default::default() };
+    let ast = crate::parse_to_ast(String::from(": a : **b**\n: <c> : d")).unwrap();
+    assert_eq!(render_with_options(ast, &options), "<dl><dt>a</dt><dd><strong>b</strong></dd><dt>&lt;c&gt;</dt><dd>d</dd></dl>");
+  }
+
+  #[test]
+  fn test_align() {
+    let options = RenderOptions { fragment: true, ..Default::default() };
+    let ast = crate::parse_to_ast(String::from("[[=]]\n|| a ||\n[[/=]]\n[[==]]\nb\n[[/==]]")).unwrap();
+    assert_eq!(render_with_options(ast, &options), concat!(
+      r#"<div style="text-align: center"><table class="wiki-content-table"><tr><td> a </td></tr></table></div>"#,
+      r#"<div style="text-align: justify"><p>b</p></div>"#,
+    ));
+  }
+
+  #[test]
+  fn test_separators() {
+    let options = RenderOptions { fragment: true, ..Default::default() };
+    let ast = crate::parse_to_ast(String::from("a\n----\n~~~~<\n[[clearfloat]]")).unwrap();
+    assert_eq!(render_with_options(ast, &options), concat!(
+      r#"<p>a</p><hr /><div style="clear: left; height: 0px; font-size: 1px"></div>"#,
+      r#"<div style="clear: both; height: 0px; font-size: 1px"></div>"#,
+    ));
+  }
 
-  res.into()
-}
\ No newline at end of file
+  #[test]
+  fn test_image() {
+    let options = RenderOptions { fragment: true, ..Default::default() };
+    let ast = crate::parse_to_ast(String::from(concat!(
+      "[[image a.png]] ",
+      r#"[[f<image /Other/b.png link="Some Page" alt="B" title="t" width="100px" height="50" class="x" style="border: 1px; position: fixed"]] "#,
+      r#"[[=image https://example.com/c.png link="javascript://%0aalert(1)"]]"#,
+    ))).unwrap();
+    assert_eq!(render_with_options(ast, &options), concat!(
+      r#"<p><img src="a.png" alt="a.png" class="image" /> "#,
+      r#"<span class="image-container floatleft"><a href="/some-page">"#,
+      r#"<img src="/local--files/other/b.png" alt="B" class="image x" title="t" width="100" height="50" style="border: 1px" /></a></span> "#,
+      r#"<span class="image-container aligncenter"><img src="https://example.com/c.png" alt="c.png" class="image" /></span></p>"#,
+    ));
+
+    let resolver = super::super::resolver::WikidotImageSourceResolver { page: Some("Page") };
+    let options = RenderOptions { fragment: true, image_source_resolver: &resolver, ..Default::default() };
+    let ast = crate::parse_to_ast(String::from("[[image a.png]]")).unwrap();
+    assert_eq!(render_with_options(ast, &options), r#"<p><img src="/local--files/page/a.png" alt="a.png" class="image" /></p>"#);
+  }
+}
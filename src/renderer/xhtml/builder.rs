@@ -1,13 +1,54 @@
+/// Elements whose surrounding whitespace does not change the rendered page.
+fn is_block(tag: &str) -> bool {
+  matches!(tag,
+    "html" | "head" | "body" | "title" | "meta" | "link" | "style"
+      | "div" | "p" | "blockquote" | "pre" | "hr" | "ul" | "ol" | "li" | "dl" | "dt" | "dd"
+      | "table" | "tbody" | "tr" | "td" | "th" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
 pub struct Builder {
   data: String,
   st: Vec<String>,
+  syntax: super::Syntax,
+  pretty: bool,
+  has_block_children: Vec<bool>, // stと同じ深さ
+  preformatted: usize, // 開いている<pre>の数
 }
 
 impl Builder {
-  pub fn new() -> Self {
+  pub fn new(syntax: super::Syntax) -> Self {
     Self {
       data: String::new(),
       st: vec![],
+      syntax,
+      pretty: false,
+      has_block_children: vec![],
+      preformatted: 0,
+    }
+  }
+
+  /// Puts block-level elements on their own lines, indented by depth.
+  /// Inline elements and the contents of `<pre>` are written exactly as without it.
+  pub fn pretty(mut self, pretty: bool) -> Self {
+    self.pretty = pretty;
+    self
+  }
+
+  /// Starts a new line before a block-level element.
+  fn break_before(&mut self, key: &str) {
+    if !is_block(key) {
+      return;
+    }
+    if let Some(v) = self.has_block_children.last_mut() {
+      *v = true;
+    }
+    self.line_break();
+  }
+
+  fn line_break(&mut self) {
+    if self.pretty && self.preformatted == 0 && !self.data.is_empty() {
+      self.data.push('\n');
+      self.data += &"  ".repeat(self.st.len());
     }
   }
 
@@ -22,6 +63,7 @@ impl Builder {
 
   /// Open a new tag with given attributes.
   pub fn open(&mut self, key: String, values: Vec<(&str, &str)>) {
+    self.break_before(&key);
     let mut st = String::from("<");
     st += &key;
     let is_values_empty = values.is_empty();
@@ -40,11 +82,16 @@ impl Builder {
     st += ">";
 
     self.data += &st;
+    if key == "pre" {
+      self.preformatted += 1;
+    }
     self.st.push(key);
+    self.has_block_children.push(false);
   }
 
   /// Inserts a self-closing element.
   pub fn insert(&mut self, key: String, values: Vec<(&str, &str)>) {
+    self.break_before(&key);
     let mut st = String::from("<");
     st += &key;
     let is_values_empty = values.is_empty();
@@ -60,20 +107,35 @@ impl Builder {
     }
 
     if !is_values_empty {st.pop().unwrap();}
-    st += " />";
+    st += match self.syntax {
+      super::Syntax::Xhtml => " />",
+      super::Syntax::Html5 => ">",
+    };
 
     self.data += &st;
   }
 
   pub fn close(&mut self) {
+    let key = self.st.pop().unwrap_or_default();
+    if key == "pre" {
+      self.preformatted -= 1;
+    }
+    if self.has_block_children.pop().unwrap_or_default() {
+      self.line_break();
+    }
     self.data += "</";
-    self.data += &self.st.pop().unwrap_or(String::new());
+    self.data += &key;
     self.data += ">";
   }
 
   pub fn write(&mut self, text: &str) {
     self.data += &Self::escape_chars(text);
   }
+
+  /// Writes already-escaped markup as is.
+  pub fn write_raw(&mut self, markup: &str) {
+    self.data += markup;
+  }
 }
 
 impl From<Builder> for String {
@@ -0,0 +1,120 @@
+/// A place in the source text. `offset` is a byte offset into the string passed to the parser;
+/// `line` and `column` are 1-based, columns count characters, and CRLF counts as one line break.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+  pub offset: usize,
+  pub line: usize,
+  pub column: usize,
+}
+
+impl std::fmt::Display for Position {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}", self.line, self.column)
+  }
+}
+
+/// The half-open range `start..end` of the source that produced a token or node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+  pub start: Position,
+  pub end: Position,
+}
+
+impl Span {
+  /// The smallest span covering both `self` and `other`.
+  pub fn merge(self, other: Span) -> Span {
+    Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+  }
+
+  /// An empty span at `at`.
+  pub fn empty(at: Position) -> Span {
+    Span { start: at, end: at }
+  }
+}
+
+/// Spans of a list of nodes, kept apart from the nodes so that trees parsed from different sources still compare equal.
+///
+/// `children` lines up with the node's children one to one: for a `TreeElement` that is
+/// `TreeElement::children()`, so table cells' contents appear row by row.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanTree {
+  pub span: Span,
+  pub children: Vec<SpanTree>,
+}
+
+impl SpanTree {
+  pub fn leaf(span: Span) -> Self {
+    Self { span, children: vec![] }
+  }
+
+  /// A tree covering all of `children`; `None` when there are none.
+  pub(crate) fn covering(children: Vec<SpanTree>) -> Option<Self> {
+    let span = children.iter().map(|v| v.span).reduce(Span::merge)?;
+    Some(Self { span, children })
+  }
+}
+
+/// Splits the source into characters with `\r\n` and `\r` turned into `\n`, and maps character indices back to positions.
+pub(crate) struct SourceMap {
+  byte_offsets: Vec<usize>, // 正規化後のi文字目が元の文字列の何バイト目か. 末尾に全体の長さを置く
+  line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+  pub fn new(s: &str) -> (Vec<char>, Self) {
+    let mut chars = vec![];
+    let mut byte_offsets = vec![];
+    let mut line_starts = vec![0];
+
+    let mut iter = s.char_indices().peekable();
+    while let Some((offset, c)) = iter.next() {
+      let c = if c == '\r' {
+        if let Some((_, '\n')) = iter.peek() {
+          iter.next(); // CRLF -> LF
+        }
+        '\n'
+      } else {
+        c
+      };
+
+      chars.push(c);
+      byte_offsets.push(offset);
+      if c == '\n' {
+        line_starts.push(chars.len());
+      }
+    }
+    byte_offsets.push(s.len());
+
+    (chars, Self { byte_offsets, line_starts })
+  }
+
+  /// Position of the `index`-th character; `index` may be the number of characters for the end of the source.
+  pub fn position(&self, index: usize) -> Position {
+    let index = index.min(self.byte_offsets.len() - 1);
+    let line = self.line_starts.partition_point(|v| *v <= index);
+    Position {
+      offset: self.byte_offsets[index],
+      line,
+      column: index - self.line_starts[line - 1] + 1,
+    }
+  }
+
+  pub fn span(&self, start: usize, end: usize) -> Span {
+    Span { start: self.position(start), end: self.position(end) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_source_map() {
+    let (chars, map) = SourceMap::new("ab\r\nč\rd");
+    assert_eq!(chars, vec!['a', 'b', '\n', 'č', '\n', 'd']);
+    assert_eq!(map.position(1), Position { offset: 1, line: 1, column: 2 });
+    assert_eq!(map.position(3), Position { offset: 4, line: 2, column: 1 });
+    assert_eq!(map.position(5), Position { offset: 7, line: 3, column: 1 });
+    assert_eq!(map.position(6), Position { offset: 8, line: 3, column: 2 });
+  }
+}
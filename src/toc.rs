@@ -0,0 +1,109 @@
+use crate::ast::TreeElement;
+
+/// A heading listed in the table of contents, with the headings under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+  pub level: u8,
+  /// The `id` of the heading: `toc0`, `toc1`, ...
+  pub anchor: String,
+  pub title: Vec<TreeElement>,
+  pub children: Vec<Entry>,
+}
+
+impl Entry {
+  /// The text of the title, as shown in the table of contents. Footnotes are left out.
+  pub fn text(&self) -> String {
+    let mut res = String::new();
+    let mut stack: Vec<&TreeElement> = self.title.iter().rev().collect();
+    while let Some(v) = stack.pop() {
+      match v {
+        TreeElement::Text(text) => res += text,
+        TreeElement::Link { name, .. } => res += name,
+        TreeElement::InternalLink { href, name, .. } => res += if name.is_empty() { href } else { name },
+        TreeElement::Footnote { .. } => {}
+        v => stack.extend(v.children().into_iter().rev()),
+      }
+    }
+    res
+  }
+}
+
+/// The headings of the page that have an anchor (`+` but not `+*`), in document order.
+/// A heading holds the headings after it up to the next one of the same or a higher level.
+pub fn outline(ast: &[TreeElement]) -> Vec<Entry> {
+  let mut roots = vec![];
+  let mut open: Vec<Entry> = vec![]; // まだ子を受け取る見出し. 下ほど浅い
+
+  let mut stack: Vec<&TreeElement> = ast.iter().rev().collect();
+  while let Some(v) = stack.pop() {
+    if let TreeElement::Heading { level, children, anchor: Some(anchor) } = v {
+      while let Some(last) = open.last() && last.level >= *level {
+        close_last(&mut open, &mut roots);
+      }
+      open.push(Entry { level: *level, anchor: anchor.clone(), title: children.clone(), children: vec![] });
+    }
+    stack.extend(v.children().into_iter().rev());
+  }
+  while !open.is_empty() {
+    close_last(&mut open, &mut roots);
+  }
+
+  roots
+}
+
+fn close_last(open: &mut Vec<Entry>, roots: &mut Vec<Entry>) {
+  if let Some(entry) = open.pop() {
+    match open.last_mut() {
+      Some(parent) => parent.children.push(entry),
+      None => roots.push(entry),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `(anchor, text, children)`
+  fn outline(s: &str) -> Vec<(String, String, usize)> {
+    let mut res = vec![];
+    let outline = super::outline(&crate::parse_to_ast(String::from(s)).unwrap());
+    let mut stack: Vec<&Entry> = outline.iter().rev().collect();
+    while let Some(v) = stack.pop() {
+      res.push((v.anchor.clone(), v.text(), v.children.len()));
+      stack.extend(v.children.iter().rev());
+    }
+    res
+  }
+
+  fn entry(anchor: &str, text: &str, children: usize) -> (String, String, usize) {
+    (String::from(anchor), String::from(text), children)
+  }
+
+  #[test]
+  fn test_outline() {
+    assert_eq!(outline("+ A\n++ B\n+++ C\n++ D\n+* Hidden\n+ E"), vec![
+      entry("toc0", "A", 2),
+      entry("toc1", "B", 1),
+      entry("toc2", "C", 0),
+      entry("toc3", "D", 0),
+      entry("toc4", "E", 0),
+    ]);
+  }
+
+  #[test]
+  fn test_outline_skipped_levels() {
+    // 深い見出しから始まっても, 後の浅い見出しの子にはならない
+    assert_eq!(outline("+++ A\n+ B\n+++ C\n++ D"), vec![
+      entry("toc0", "A", 0),
+      entry("toc1", "B", 2),
+      entry("toc2", "C", 0),
+      entry("toc3", "D", 0),
+    ]);
+  }
+
+  #[test]
+  fn test_title_text() {
+    assert_eq!(outline("[[div]]\n+ **a** [[[page|b]]][[footnote]]c[[/footnote]]\n[[/div]]"), vec![entry("toc0", "a b", 0)]);
+  }
+}
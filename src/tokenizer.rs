@@ -11,19 +11,29 @@ pub enum Token {
   Subscript, // ,,
   ElementBegin{name: String, attributes: Vec<(String, String)>}, // [[span style="color:red"]]
   ElementEnd(String), // [[/span]]
+  Code{language: Option<String>, source: String}, // [[code type="..."]] ~ [[/code]], the body is not tokenized
   ColoredBeginColorCode(String), // ##color|
   ColoredBeginColorName(String), // TODO: Enum化も検討
   ColoredEnd, // ##
   NamedLink{link: String, name: String},
   PageLink{link: String, name: String},
+  Image{alignment: Option<crate::ast::Alignment>, float: bool, source: String, attributes: Vec<(String, String)>}, // [[f<image source alt="..."]]
   BlockQuote(std::num::NonZeroUsize), // TODO: u8で管理していた頃の名残のコードを吹き飛ばす
+  Heading{level: u8, in_toc: bool}, // + ~ ++++++, +* is not listed in TOC
+  ListItem{kind: crate::ast::ListKind, depth: std::num::NonZeroUsize}, // * or #, depth is (leading spaces + 1)
   CellSeparator(Option<crate::ast::table_cell::Style>), // ||[~<=>]?
+  HorizontalRule, // ---- (4 or more) on its own line
+  ClearFloat(Option<crate::ast::Float>), // ~~~~, ~~~~< or ~~~~> on its own line. None clears both sides
+  DefinitionTerm, // ": " at the start of a line that has a " : " after it
+  DefinitionSeparator, // the first " : " after DefinitionTerm
   NewLine, // \n
 
   Text(String)
 }
 
 use crate::ast::ParseFrame;
+use crate::lint::{Warning, WarningKind};
+use crate::span::{SourceMap, Span};
 impl TryFrom<Token> for crate::ast::ParseFrame {
   type Error = ();
 
@@ -39,13 +49,21 @@ impl TryFrom<Token> for crate::ast::ParseFrame {
         Token::Subscript => Ok(ParseFrame::Subscript),
         Token::ElementBegin { name: _, attributes: _ } => Err(()),
         Token::ElementEnd(_) => Err(()),
+        Token::Code { language: _, source: _ } => Err(()),
         Token::ColoredBeginColorName(_) => Err(()),
         Token::ColoredBeginColorCode(_) => Err(()),
         Token::ColoredEnd => Err(()),
         Token::NamedLink { link: _, name: _ } => Err(()),
         Token::PageLink { link: _, name: _ } => Err(()),
+        Token::Image { .. } => Err(()),
         Token::BlockQuote(_) => Err(()),
+        Token::Heading { level: _, in_toc: _ } => Err(()),
+        Token::ListItem { kind: _, depth: _ } => Err(()),
         Token::CellSeparator(_) => Err(()),
+        Token::HorizontalRule => Err(()),
+        Token::ClearFloat(_) => Err(()),
+        Token::DefinitionTerm => Err(()),
+        Token::DefinitionSeparator => Err(()),
         Token::NewLine => Err(()),
         Token::Text(_) => Err(()),
       }
@@ -53,36 +71,70 @@ impl TryFrom<Token> for crate::ast::ParseFrame {
 }
 
 
-struct TokenData {
-  res: Vec<Token>,
+struct TokenData<'a> {
+  res: Vec<(Token, Span)>,
   buf: String,
+  map: &'a SourceMap,
+  cursor: usize, // 今読んでいる位置. トークンやテキストはここから始まる
+  buf_start: usize,
+  last_token_start: Option<usize>, // 終わりがまだ決まっていないトークン
 }
 
-impl TokenData {
-  fn new() -> Self {
+impl<'a> TokenData<'a> {
+  fn new(map: &'a SourceMap) -> Self {
     Self {
       res: vec![],
       buf: String::new(),
+      map,
+      cursor: 0,
+      buf_start: 0,
+      last_token_start: None,
     }
   }
 
+  /// Called before reading at `at`. Everything read since the last token belongs to it.
+  fn advance(&mut self, at: usize) {
+    if let Some(start) = self.last_token_start.take() && let Some((_, span)) = self.res.last_mut() {
+      *span = self.map.span(start, at);
+    }
+    self.cursor = at;
+  }
+
   fn add_char(&mut self, c: char) {
+    if self.buf.is_empty() {
+      self.buf_start = self.cursor;
+    }
     self.buf.push(c);
   }
 
   fn flush(&mut self) {
     if !self.buf.is_empty() {
       let text = std::mem::take(&mut self.buf);
-      self.res.push(Token::Text(text));
+      self.res.push((Token::Text(text), self.map.span(self.buf_start, self.cursor)));
     }
   }
 
   fn flush_and_add_token(&mut self, t: Token) {
     self.flush();
-    self.res.push(t);
+    self.res.push((t, Span::empty(self.map.position(self.cursor))));
+    self.last_token_start = Some(self.cursor);
+  }
+
+  /// Turns the token at `at` back into `text`, joined to the text right after it.
+  fn revert_to_text(&mut self, at: usize, text: &str) {
+    self.flush();
+    let span = self.res[at].1;
+    if let Some((Token::Text(next), next_span)) = self.res.get_mut(at + 1) {
+      next.insert_str(0, text);
+      *next_span = span.merge(*next_span);
+      self.res.remove(at);
+    } else {
+      self.res[at].0 = Token::Text(String::from(text));
+    }
   }
 
-  fn get_value(mut self) -> Vec<Token> {
+  fn get_value(mut self, end: usize) -> Vec<(Token, Span)> {
+    self.advance(end);
     self.flush();
     self.res
   }
@@ -113,13 +165,196 @@ fn get_unescaped_string(s: &[char]) -> String {
   target_str
 }
 
-// TODO \n|の処理を書く
-pub fn tokenize(s: String) -> Vec<Token> {
-  let mut data: TokenData = TokenData::new();
+/// 行頭の`+ `から`++++++ `までを見出しとして解釈する. `+* `は目次に載らない.
+fn tokenize_heading(chars: &[char]) -> Option<(Token, usize)> {
+  let level = chars.iter().take_while(|c| **c == '+').count();
+  if !(1..=6).contains(&level) {
+    return None;
+  }
+
+  let mut len = level;
+  let in_toc = if chars.get(len) == Some(&'*') {
+    len += 1;
+    false
+  } else {
+    true
+  };
+
+  if chars.get(len) != Some(&' ') {
+    return None;
+  }
+
+  Some((Token::Heading { level: level as u8, in_toc }, len + 1))
+}
+
+/// 行頭の`* `, `# `をリスト項目として解釈する. 先頭の空白の数+1が深さになる.
+fn tokenize_list_item(chars: &[char]) -> Option<(Token, usize)> {
+  let spaces = chars.iter().take_while(|c| **c == ' ').count();
+
+  let kind = match chars.get(spaces) {
+    Some('*') => crate::ast::ListKind::Unordered,
+    Some('#') => crate::ast::ListKind::Ordered,
+    _ => { return None; }
+  };
+
+  if chars.get(spaces + 1) != Some(&' ') {
+    return None;
+  }
+
+  let depth = std::num::NonZeroUsize::new(spaces + 1)?;
+  Some((Token::ListItem { kind, depth }, spaces + 2))
+}
+
+/// 4つ以上の`-`だけの行を水平線, 4つ以上の`~`(と`<`か`>`)だけの行を回り込みの解除として解釈する.
+/// 行末の改行は消費しない.
+fn tokenize_separator(chars: &[char]) -> Option<(Token, usize)> {
+  let line = chars.split(|c| *c == '\n').next().unwrap_or_default();
+  let count = |c: char| line.iter().take_while(|v| **v == c).count();
+
+  if count('-') >= 4 && count('-') == line.len() {
+    return Some((Token::HorizontalRule, line.len()));
+  }
+
+  let tildes = count('~');
+  if tildes < 4 {
+    return None;
+  }
+  let float = match &line[tildes..] {
+    [] => None,
+    ['<'] => Some(crate::ast::Float::Left),
+    ['>'] => Some(crate::ast::Float::Right),
+    _ => { return None; }
+  };
+  Some((Token::ClearFloat(float), line.len()))
+}
+
+/// 行頭の`: `を定義リストの項目として解釈する. 同じ行に用語と説明を分ける` : `がなければ項目にしない.
+fn tokenize_definition_term(chars: &[char]) -> Option<(Token, usize)> {
+  if !chars.starts_with(&[':', ' ']) {
+    return None;
+  }
+  let line = chars.split(|c| *c == '\n').next().unwrap_or_default();
+  line[2..].windows(3).any(|w| w == [' ', ':', ' ']).then_some((Token::DefinitionTerm, 2))
+}
+
+/// `[[code]]`の直後から`[[/code]]`までをそのまま取り出す. 前後の改行1つずつは取り除く.
+/// 戻り値の2つ目は`[[/code]]`を含めて消費した文字数.
+fn capture_code_body(chars: &[char]) -> Option<(String, usize)> {
+  let end_tag: Vec<char> = "[[/code]]".chars().collect();
+  let end = chars.windows(end_tag.len()).position(|w| w == end_tag.as_slice())?;
+
+  let mut body = &chars[..end];
+  if let Some(('\n', rest)) = body.split_first() {
+    body = rest;
+  }
+  if let Some(('\n', rest)) = body.split_last() {
+    body = rest;
+  }
 
+  Some((body.iter().collect(), end + end_tag.len()))
+}
+
+/// `span id="box" style="color: red" checked`を要素名と属性に分ける.
+/// 値のない語はキーが空の属性になる. 引用符の外の`|`は空白として扱う.
+fn parse_element_specifier(s: &str) -> (String, Vec<(String, String)>) {
   let chars: Vec<char> = s.chars().collect();
+  let is_separator = |c: char| c.is_whitespace() || c == '|';
+
+  let mut words: Vec<(String, Option<String>)> = vec![]; // (key or bare word, value)
+  let mut i = 0;
+  while i < chars.len() {
+    if is_separator(chars[i]) {
+      i += 1;
+      continue;
+    }
+
+    let mut key = String::new();
+    while i < chars.len() && !is_separator(chars[i]) && chars[i] != '=' {
+      key.push(chars[i]);
+      i += 1;
+    }
+
+    if i < chars.len() && chars[i] == '=' {
+      i += 1;
+      let mut value = String::new();
+      if i < chars.len() && (chars[i] == '"' || chars[i] == '\'') {
+        let quote = chars[i];
+        i += 1;
+        while i < chars.len() && chars[i] != quote {
+          value.push(chars[i]);
+          i += 1;
+        }
+        i += 1; // closing quote
+      } else {
+        while i < chars.len() && !is_separator(chars[i]) {
+          value.push(chars[i]);
+          i += 1;
+        }
+      }
+      words.push((key, Some(value)));
+    } else {
+      words.push((key, None));
+    }
+  }
+
+  let mut words = words.into_iter();
+  let name = match words.next() {
+    Some((name, _)) => name,
+    None => String::new(),
+  };
+  let attributes = words.map(|(k, v)| match v {
+    Some(v) => (k, v),
+    None => (String::new(), k),
+  }).collect();
+
+  (name, attributes)
+}
+
+/// `[[image source ...]]` with an optional `<`, `>`, `=`, `f<` or `f>` before `image`.
+fn tokenize_image(specifier: &str) -> Option<Token> {
+  use crate::ast::Alignment;
+
+  let (alignment, float, rest) = if let Some(v) = specifier.strip_prefix("f<") {
+    (Some(Alignment::Left), true, v)
+  } else if let Some(v) = specifier.strip_prefix("f>") {
+    (Some(Alignment::Right), true, v)
+  } else if let Some(v) = specifier.strip_prefix('<') {
+    (Some(Alignment::Left), false, v)
+  } else if let Some(v) = specifier.strip_prefix('>') {
+    (Some(Alignment::Right), false, v)
+  } else if let Some(v) = specifier.strip_prefix('=') {
+    (Some(Alignment::Center), false, v)
+  } else {
+    (None, false, specifier)
+  };
+
+  let (name, attributes) = parse_element_specifier(rest);
+  if name != "image" || !rest.starts_with("image") { // "= image"は画像ではない
+    return None;
+  }
+
+  // 最初の値のない語がファイル名
+  let mut source = None;
+  let attributes = attributes.into_iter().filter(|(key, value)| {
+    if key.is_empty() && source.is_none() {
+      source = Some(value.clone());
+      return false;
+    }
+    true
+  }).collect();
+
+  Some(Token::Image { alignment, float, source: source.unwrap_or_default(), attributes })
+}
+
+/// Splits the source into tokens, each with the span of source it was read from.
+/// `\r\n` and `\r` are read as `\n`. A `[[` that is never closed is read as text and reported in `warnings`.
+// TODO \n|の処理を書く
+pub fn tokenize(s: String, warnings: &mut Vec<Warning>) -> Vec<(Token, Span)> {
+  let (chars, map) = SourceMap::new(&s);
+  let mut data: TokenData = TokenData::new(&map);
 
   let mut is_escaping_parse = false;
+  let mut definition_term = None; // 行頭の": "の後で, まだ" : "を読んでいなければそのトークンの位置
 
   // TODO: optimize by making this static
   let tokenize_if_double: Vec<(char, Token)> = vec![
@@ -135,6 +370,8 @@ pub fn tokenize(s: String) -> Vec<Token> {
 
   let mut i = 0;
   'chars_loop: while i < chars.len() {
+    data.advance(i);
+
     // check escape
     if chars[i] == '@' && is_next_eq(i, &chars, '@') {
       i += 2;
@@ -148,6 +385,24 @@ pub fn tokenize(s: String) -> Vec<Token> {
       continue 'chars_loop;
     }
 
+    // 行頭でのみ有効な記法
+    if (i == 0 || chars[i-1] == '\n') && let Some((token, len)) = tokenize_heading(&chars[i..])
+      .or_else(|| tokenize_list_item(&chars[i..]))
+      .or_else(|| tokenize_separator(&chars[i..]))
+      .or_else(|| tokenize_definition_term(&chars[i..])) {
+      data.flush_and_add_token(token);
+      definition_term = (data.res.last().map(|(v, _)| v) == Some(&Token::DefinitionTerm)).then(|| data.res.len() - 1);
+      i += len;
+      continue 'chars_loop;
+    }
+
+    if definition_term.is_some() && chars[i..].starts_with(&[' ', ':', ' ']) {
+      definition_term = None;
+      data.flush_and_add_token(Token::DefinitionSeparator);
+      i += 3;
+      continue 'chars_loop;
+    }
+
     for (c, t) in &tokenize_if_double {
       if chars[i] == *c && is_next_eq(i, &chars, *c) {
         data.flush_and_add_token(t.clone());
@@ -158,12 +413,15 @@ pub fn tokenize(s: String) -> Vec<Token> {
 
     match chars[i] {
       // TODO: is_in_double_quotationを実装
-      '[' => 'square_brace: {
+      '[' => {
+        'square_brace: {
         if is_next_eq(i, &chars, '[') {
           if is_next_eq(i+1, &chars, '[') {
             let mut elem_specifier_len = 0;
+            let mut is_closed = false;
             while i+3+elem_specifier_len < chars.len() { // i+3+elem_specifier_lenに本体を伸ばせるかを見る
               if chars[i+2+elem_specifier_len] != '\\' && chars[i+3+elem_specifier_len] == ']' && is_next_eq(i+3+elem_specifier_len, &chars, ']') && is_next_eq(i+3+elem_specifier_len+1, &chars, ']') {
+                is_closed = true;
                 break;
               }
               if chars[i+3+elem_specifier_len] == '\n' {
@@ -171,13 +429,14 @@ pub fn tokenize(s: String) -> Vec<Token> {
               }
               elem_specifier_len+=1;
             };
+            if !is_closed {
+              break 'square_brace;
+            }
 
             let target_str: String = get_unescaped_string(&chars[i+3..i+3+elem_specifier_len]);
 
-            if target_str.contains('|') {
-              let v: (&str, &str) = target_str.split_once('|').unwrap();
-
-              data.flush_and_add_token(Token::PageLink { link: String::from(v.0), name: String::from(v.1) });
+            if let Some((link, name)) = target_str.split_once('|') {
+              data.flush_and_add_token(Token::PageLink { link: String::from(link), name: String::from(name) });
             } else {
               data.flush_and_add_token(Token::PageLink { link: target_str, name: String::from("") });
             }
@@ -187,34 +446,39 @@ pub fn tokenize(s: String) -> Vec<Token> {
           } else {
             // elem_begin
             let mut elem_specifier_len = 0;
+            let mut is_closed = false;
             while i+2+elem_specifier_len < chars.len() { // i+2+elem_specifier_lenに本体を伸ばせるかを見る
               if chars[i+1+elem_specifier_len] != '\\' && chars[i+2+elem_specifier_len] == ']' && is_next_eq(i+2+elem_specifier_len, &chars, ']') {
+                is_closed = true;
                 break;
               }
               // \nは許す
               elem_specifier_len+=1;
             };
+            if !is_closed {
+              warnings.push(Warning { kind: WarningKind::UnterminatedElement, span: map.span(i, i + 2) });
+              data.add_char('[');
+              data.add_char('[');
+              i += 2;
+              continue 'chars_loop;
+            }
 
             let target_str: String = get_unescaped_string(&chars[i+2..i+2+elem_specifier_len]);
 
-            if target_str.starts_with("/") { // 閉じタグ
-              data.flush_and_add_token(Token::ElementEnd(target_str.strip_prefix("/").unwrap().into()));
+            if let Some(name) = target_str.strip_prefix('/') { // 閉じタグ
+              data.flush_and_add_token(Token::ElementEnd(name.into()));
+            } else if let Some(token) = tokenize_image(&target_str) {
+              data.flush_and_add_token(token);
+            } else if let name @ ("<" | ">" | "=" | "==") = target_str.trim() { // parse_element_specifierでは=が属性の区切りになる
+              data.flush_and_add_token(Token::ElementBegin { name: String::from(name), attributes: vec![] });
             } else {
-              let mut name = String::new();
-              let mut attributes: Vec<(String, String)> = vec![];
-
-              for (at, v) in target_str.replace("|", " ").split_whitespace().enumerate() {
-                if at == 0 {
-                  name = String::from(v);
-                }
-                else if v.contains('=') {
-                  let v: Vec<&str> = v.splitn(2, '=').collect();
-                  if v.len() == 2 && v[1].len() >= 2 {
-                    attributes.push((String::from(v[0]), String::from(&v[1][1..(v[1].len()-1)])));
-                  }
-                } else {
-                  attributes.push((String::from(""), String::from(v)));
-                }
+              let (name, attributes) = parse_element_specifier(&target_str);
+
+              if name == "code" && let Some((source, len)) = chars.get(i+2+elem_specifier_len+2..).and_then(capture_code_body) {
+                let language = attributes.iter().find(|(k, _)| k == "type").map(|(_, v)| v.clone());
+                data.flush_and_add_token(Token::Code { language, source });
+                i += 2 + elem_specifier_len + 2 + len;
+                continue 'chars_loop;
               }
 
               data.flush_and_add_token(Token::ElementBegin { name, attributes });
@@ -224,9 +488,11 @@ pub fn tokenize(s: String) -> Vec<Token> {
           }
         } else {
           let mut elem_specifier_len = 0;
+          let mut is_closed = false;
 
           while i+1+elem_specifier_len < chars.len() { // i+1+elem_specifier_lenに本体を伸ばせるかを見る
             if chars[i+elem_specifier_len] != '\\' && chars[i+1+elem_specifier_len] == ']' {
+              is_closed = true;
               break;
             }
             if chars[i+1+elem_specifier_len] == '\n' {
@@ -234,6 +500,9 @@ pub fn tokenize(s: String) -> Vec<Token> {
             }
             elem_specifier_len+=1;
           };
+          if !is_closed {
+            break 'square_brace;
+          }
 
           let target_str = get_unescaped_string(&chars[i+1..i+1+elem_specifier_len]);
 
@@ -247,6 +516,9 @@ pub fn tokenize(s: String) -> Vec<Token> {
             continue 'chars_loop;
           }
         }
+        }
+        // リンクにならなかった [ はそのまま出す
+        data.add_char('[');
       }
 
       // TODO: いくつかの他の記号に対応
@@ -268,6 +540,8 @@ pub fn tokenize(s: String) -> Vec<Token> {
             data.flush_and_add_token(Token::CellSeparator(None));
             i += 1;
           }
+        } else {
+          data.add_char('|');
         }
       }
 
@@ -287,11 +561,13 @@ pub fn tokenize(s: String) -> Vec<Token> {
           while is_next_eq(level - 1 + i, &chars, '>') {
             level += 1;
           }
-          if i+level >= chars.len() || chars[i+level] == ' ' {
-            data.flush_and_add_token(Token::BlockQuote(level.try_into().unwrap())); // never overflows
-            i += level;
+          if (i+level >= chars.len() || chars[i+level] == ' ') && let Some(depth) = std::num::NonZeroUsize::new(level) {
+            data.flush_and_add_token(Token::BlockQuote(depth));
+            i += level + 1; // ' ' も読み飛ばす
+            continue 'chars_loop;
           }
         }
+        data.add_char('>');
       }
 
       '#' => 'sharp_match: {
@@ -346,10 +622,16 @@ pub fn tokenize(s: String) -> Vec<Token> {
 
           data.flush_and_add_token(Token::ColoredEnd);
           i+=1;
+        } else {
+          data.add_char('#');
         }
       }
 
       '\n' => {
+        // 先読みした" : "が要素の中にあって区切りにならなかった
+        if let Some(at) = definition_term.take() {
+          data.revert_to_text(at, ": ");
+        }
         data.flush_and_add_token(Token::NewLine);
       }
 
@@ -361,14 +643,19 @@ pub fn tokenize(s: String) -> Vec<Token> {
     i += 1;
   }
 
-  data.get_value()
+  let end = i.min(chars.len());
+  if let Some(at) = definition_term {
+    data.advance(end);
+    data.revert_to_text(at, ": ");
+  }
+  data.get_value(end)
 }
 
 #[cfg(test)]
 mod test {
   use super::Token;
   fn tokenize(s: &str) -> Vec<super::Token> {
-    super::tokenize(String::from(s))
+    super::tokenize(String::from(s), &mut vec![]).into_iter().map(|(token, _)| token).collect()
   }
 
   #[test]
@@ -460,6 +747,59 @@ mod test {
     ])
   }
 
+  #[test]
+  fn test_element_quoted_attributes() {
+    assert_eq!(tokenize(r#"[[span style="color: red; font-weight: bold" class='a b']]x[[/span]]"#), vec![
+      Token::ElementBegin {
+        name: String::from("span"),
+        attributes: vec![
+          (String::from("style"), String::from("color: red; font-weight: bold")),
+          (String::from("class"), String::from("a b")),
+        ],
+      },
+      Token::Text(String::from("x")),
+      Token::ElementEnd(String::from("span")),
+    ])
+  }
+
+  #[test]
+  fn test_image() {
+    use crate::ast::Alignment;
+
+    assert_eq!(tokenize(r#"[[image a.png]][[f<image b.png link="page" alt="B"]][[=image https://example.com/c.png]][[image]]"#), vec![
+      Token::Image { alignment: None, float: false, source: String::from("a.png"), attributes: vec![] },
+      Token::Image {
+        alignment: Some(Alignment::Left),
+        float: true,
+        source: String::from("b.png"),
+        attributes: vec![(String::from("link"), String::from("page")), (String::from("alt"), String::from("B"))],
+      },
+      Token::Image { alignment: Some(Alignment::Center), float: false, source: String::from("https://example.com/c.png"), attributes: vec![] },
+      Token::Image { alignment: None, float: false, source: String::new(), attributes: vec![] },
+    ]);
+    assert_eq!(tokenize("[[images]]"), vec![Token::ElementBegin { name: String::from("images"), attributes: vec![] }]);
+  }
+
+  #[test]
+  fn test_code() {
+    assert_eq!(tokenize("a\n[[code type=\"rust\"]]\nlet s = \"**not bold**\"; // [[span]]\n[[/code]]\nb"), vec![
+      Token::Text(String::from("a")),
+      Token::NewLine,
+      Token::Code { language: Some(String::from("rust")), source: String::from("let s = \"**not bold**\"; // [[span]]") },
+      Token::NewLine,
+      Token::Text(String::from("b")),
+    ]);
+  }
+
+  #[test]
+  fn test_unterminated_code() {
+    assert_eq!(tokenize("[[code]]**x"), vec![
+      Token::ElementBegin { name: String::from("code"), attributes: vec![] },
+      Token::Bold,
+      Token::Text(String::from("x")),
+    ]);
+  }
+
   #[test]
   fn test_colored_colorcode() {
     assert_eq!(tokenize("bbb##ff00ff|Material Error##aaa"), vec![
@@ -515,6 +855,105 @@ mod test {
     ]);
   }
 
+  #[test]
+  fn test_heading() {
+    assert_eq!(tokenize("+ One\n+++ Three\n+* Hidden\n++++++ Six"), vec![
+      Token::Heading { level: 1, in_toc: true }, Token::Text(String::from("One")),
+      Token::NewLine, Token::Heading { level: 3, in_toc: true }, Token::Text(String::from("Three")),
+      Token::NewLine, Token::Heading { level: 1, in_toc: false }, Token::Text(String::from("Hidden")),
+      Token::NewLine, Token::Heading { level: 6, in_toc: true }, Token::Text(String::from("Six")),
+    ]);
+  }
+
+  #[test]
+  fn test_not_heading() {
+    assert_eq!(tokenize("a + b\n+++++++ Seven\n+NoSpace"), vec![
+      Token::Text(String::from("a + b")),
+      Token::NewLine, Token::Text(String::from("+++++++ Seven")),
+      Token::NewLine, Token::Text(String::from("+NoSpace")),
+    ]);
+  }
+
+  #[test]
+  fn test_separators() {
+    use crate::ast::Float;
+    assert_eq!(tokenize("----\n------\n~~~~\n~~~~~<\n~~~~>"), vec![
+      Token::HorizontalRule, Token::NewLine, Token::HorizontalRule,
+      Token::NewLine, Token::ClearFloat(None),
+      Token::NewLine, Token::ClearFloat(Some(Float::Left)),
+      Token::NewLine, Token::ClearFloat(Some(Float::Right)),
+    ]);
+    assert_eq!(tokenize("---- a\n~~~ \n~~~~<>"), vec![
+      Token::Strikethrough, Token::Strikethrough, Token::Text(String::from(" a")),
+      Token::NewLine, Token::Text(String::from("~~~ ")),
+      Token::NewLine, Token::Text(String::from("~~~~<>")),
+    ]);
+  }
+
+  #[test]
+  fn test_definition() {
+    assert_eq!(tokenize(": a : b : c\n:  : d\n: e\nf : g"), vec![
+      Token::DefinitionTerm, Token::Text(String::from("a")), Token::DefinitionSeparator, Token::Text(String::from("b : c")),
+      Token::NewLine, Token::DefinitionTerm, Token::DefinitionSeparator, Token::Text(String::from("d")),
+      Token::NewLine, Token::Text(String::from(": e")),
+      Token::NewLine, Token::Text(String::from("f : g")),
+    ]);
+    assert_eq!(tokenize(": [[[a : b]]]\n: a @@ : @@"), vec![
+      Token::Text(String::from(": ")), Token::PageLink { link: String::from("a : b"), name: String::new() },
+      Token::NewLine, Token::Text(String::from(": a  : ")),
+    ]);
+  }
+
+  #[test]
+  fn test_list_item() {
+    use crate::ast::ListKind;
+    let nz = |v: usize| std::num::NonZeroUsize::new(v).unwrap();
+
+    assert_eq!(tokenize("* One\n  # Three\n*Not\n**Bold**"), vec![
+      Token::ListItem { kind: ListKind::Unordered, depth: nz(1) }, Token::Text(String::from("One")),
+      Token::NewLine, Token::ListItem { kind: ListKind::Ordered, depth: nz(3) }, Token::Text(String::from("Three")),
+      Token::NewLine, Token::Text(String::from("*Not")),
+      Token::NewLine, Token::Bold, Token::Text(String::from("Bold")), Token::Bold,
+    ]);
+  }
+
+  #[test]
+  fn test_literal_symbols() {
+    assert_eq!(tokenize("a # b | c > d [e] [f"), vec![
+      Token::Text(String::from("a # b | c > d [e] [f")),
+    ]);
+  }
+
+  #[test]
+  fn test_unterminated_element() {
+    let mut warnings = vec![];
+    let tokens: Vec<Token> = super::tokenize(String::from("ok\nab [[span **a**"), &mut warnings).into_iter().map(|(token, _)| token).collect();
+    assert_eq!(tokens, vec![
+      Token::Text(String::from("ok")), Token::NewLine, Token::Text(String::from("ab [[span ")),
+      Token::Bold, Token::Text(String::from("a")), Token::Bold,
+    ]);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].kind, crate::lint::WarningKind::UnterminatedElement);
+    assert_eq!(warnings[0].span.start, crate::span::Position { offset: 6, line: 2, column: 4 });
+  }
+
+  #[test]
+  fn test_spans() {
+    use crate::span::{Position, Span};
+    let span = |start: (usize, usize, usize), end: (usize, usize, usize)| Span {
+      start: Position { offset: start.0, line: start.1, column: start.2 },
+      end: Position { offset: end.0, line: end.1, column: end.2 },
+    };
+
+    assert_eq!(super::tokenize(String::from("**a**\r\nb\\"), &mut vec![]), vec![
+      (Token::Bold, span((0, 1, 1), (2, 1, 3))),
+      (Token::Text(String::from("a")), span((2, 1, 3), (3, 1, 4))),
+      (Token::Bold, span((3, 1, 4), (5, 1, 6))),
+      (Token::NewLine, span((5, 1, 6), (7, 2, 1))),
+      (Token::Text(String::from("b\n")), span((7, 2, 1), (9, 2, 3))),
+    ]);
+  }
+
   #[test]
   fn test_escape_parsing() {
     assert_eq!(tokenize("@@**Should not be bolded**@@"), vec![
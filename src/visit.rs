@@ -0,0 +1,543 @@
+//! Traversal of the AST. Implement only the methods for the variants you care about; the others walk into
+//! their children. An overriding method continues the walk by calling `self.visit_elements(children)` itself.
+//!
+//! The walk recurses once per level of nesting and stops at the same depth as the renderers: elements nested deeper
+//! are skipped. Start the walk with [`visit`] or [`visit_mut`] to find out whether that happened.
+
+use std::cell::Cell as StdCell;
+
+use crate::ast::{table_cell::Cell, Alignment, CssSize, Float, HideLocation, ListKind, TextAlign, TreeElement, Url};
+use crate::error::RenderError;
+use crate::renderer::MAX_RENDER_DEPTH;
+
+thread_local! {
+  // walk_element(_mut)の入れ子の深さと, 上限を超えて読み飛ばした要素があったか
+  static DEPTH: StdCell<(usize, bool)> = const { StdCell::new((0, false)) };
+}
+
+/// Visits `elements` and fails with `RenderError::NestingTooDeep` if some of them were too deep to visit.
+pub fn visit<V: Visitor + ?Sized>(visitor: &mut V, elements: &[TreeElement]) -> Result<(), RenderError> {
+  checked(|| visitor.visit_elements(elements))
+}
+
+/// Rewrites `elements` and fails with `RenderError::NestingTooDeep` if some of them were too deep to visit.
+pub fn visit_mut<V: VisitorMut + ?Sized>(visitor: &mut V, elements: &mut Vec<TreeElement>) -> Result<(), RenderError> {
+  checked(|| visitor.visit_elements(elements))
+}
+
+fn checked(walk: impl FnOnce()) -> Result<(), RenderError> {
+  // 訪問の中から別の訪問を始めても, 外側の結果を消さないようにする
+  let outer = DEPTH.with(|v| v.replace((v.get().0, false)));
+  walk();
+  let (depth, too_deep) = DEPTH.with(|v| v.get());
+  DEPTH.with(|v| v.set((depth, outer.1 || too_deep)));
+
+  if too_deep {
+    Err(RenderError::NestingTooDeep { limit: MAX_RENDER_DEPTH })
+  } else {
+    Ok(())
+  }
+}
+
+/// 1段深く入る. 上限に達していたらNoneを返し, 読み飛ばしたことを記録する. 抜けるときはdropで戻る
+struct DepthGuard;
+
+impl DepthGuard {
+  fn enter() -> Option<Self> {
+    DEPTH.with(|v| {
+      let (depth, too_deep) = v.get();
+      if depth >= MAX_RENDER_DEPTH {
+        v.set((depth, true));
+        None
+      } else {
+        v.set((depth + 1, too_deep));
+        Some(DepthGuard)
+      }
+    })
+  }
+}
+
+impl Drop for DepthGuard {
+  fn drop(&mut self) {
+    DEPTH.with(|v| {
+      let (depth, too_deep) = v.get();
+      v.set((depth - 1, too_deep));
+    });
+  }
+}
+
+/// Reads the tree.
+pub trait Visitor {
+  /// Called with every element before its variant's method.
+  fn enter(&mut self, _element: &TreeElement) {}
+  /// Called with every element after its variant's method returned.
+  fn leave(&mut self, _element: &TreeElement) {}
+
+  fn visit_elements(&mut self, elements: &[TreeElement]) {
+    for v in elements {
+      self.visit_element(v);
+    }
+  }
+
+  fn visit_element(&mut self, element: &TreeElement) {
+    walk_element(self, element);
+  }
+
+  fn visit_paragraph(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_text(&mut self, _text: &str) {}
+
+  fn visit_bold(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_italics(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_underline(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_strikethrough(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_monospaced(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_superscript(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_subscript(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_colored(&mut self, _red: u8, _green: u8, _blue: u8, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_size(&mut self, _scale: &CssSize, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_link(&mut self, _href: &Url, _open_in_new_tab: bool, _name: &str) {}
+
+  fn visit_internal_link(&mut self, _href: &str, _open_in_new_tab: bool, _name: &str) {}
+
+  fn visit_collapsible(&mut self, _show: &str, _hide: &str, _folded: bool, _hide_location: HideLocation, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_footnote(&mut self, _id: u32, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_footnote_block(&mut self, _title: Option<&str>) {}
+
+  fn visit_quote_block(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_iframe(&mut self, _raw: &str) {}
+
+  fn visit_code_block(&mut self, _language: Option<&str>, _source: &str) {}
+
+  fn visit_tab(&mut self, _title: &str, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_tab_view(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  /// Visits the cells row by row.
+  fn visit_table(&mut self, rows: &[Vec<Cell>]) {
+    for cell in rows.iter().flatten() {
+      self.visit_cell(cell);
+    }
+  }
+
+  fn visit_cell(&mut self, cell: &Cell) {
+    self.visit_elements(&cell.val);
+  }
+
+  fn visit_definition_list(&mut self, items: &[(Vec<TreeElement>, Vec<TreeElement>)]) {
+    for (term, definition) in items {
+      self.visit_elements(term);
+      self.visit_elements(definition);
+    }
+  }
+
+  fn visit_heading(&mut self, _level: u8, children: &[TreeElement], _anchor: Option<&str>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_list(&mut self, _kind: ListKind, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_list_item(&mut self, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_new_line(&mut self) {}
+
+  fn visit_html_element(&mut self, _tag: &str, _property: &[(String, String)], children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  fn visit_table_of_contents(&mut self, _float: Option<Float>) {}
+
+  fn visit_horizontal_rule(&mut self) {}
+
+  fn visit_clear_float(&mut self, _float: Option<Float>) {}
+
+  fn visit_align(&mut self, _alignment: TextAlign, children: &[TreeElement]) {
+    self.visit_elements(children);
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn visit_image(
+    &mut self, _source: &str, _alt: Option<&str>, _title: Option<&str>, _width: Option<u32>, _height: Option<u32>,
+    _link: Option<&str>, _alignment: Option<Alignment>, _float: bool, _class: Option<&str>, _style: Option<&str>,
+  ) {}
+}
+
+/// Calls `enter`, the method for the variant of `element`, then `leave`. Does nothing when `element` is nested
+/// deeper than the renderers go.
+pub fn walk_element<V: Visitor + ?Sized>(visitor: &mut V, element: &TreeElement) {
+  let Some(_guard) = DepthGuard::enter() else { return; };
+  visitor.enter(element);
+  match element {
+    TreeElement::Paragraph(children) => visitor.visit_paragraph(children),
+    TreeElement::Text(text) => visitor.visit_text(text),
+    TreeElement::Bold(children) => visitor.visit_bold(children),
+    TreeElement::Italics(children) => visitor.visit_italics(children),
+    TreeElement::Underline(children) => visitor.visit_underline(children),
+    TreeElement::Strikethrough(children) => visitor.visit_strikethrough(children),
+    TreeElement::Monospaced(children) => visitor.visit_monospaced(children),
+    TreeElement::Superscript(children) => visitor.visit_superscript(children),
+    TreeElement::Subscript(children) => visitor.visit_subscript(children),
+    TreeElement::Colored { red, green, blue, children } => visitor.visit_colored(*red, *green, *blue, children),
+    TreeElement::Size { scale, children } => visitor.visit_size(scale, children),
+    TreeElement::Link { href, open_in_new_tab, name } => visitor.visit_link(href, *open_in_new_tab, name),
+    TreeElement::InternalLink { href, open_in_new_tab, name } => visitor.visit_internal_link(href, *open_in_new_tab, name),
+    TreeElement::Collapsible { show, hide, folded, hide_location, children } => visitor.visit_collapsible(show, hide, *folded, *hide_location, children),
+    TreeElement::Footnote { id, children } => visitor.visit_footnote(*id, children),
+    TreeElement::FootnoteBlock { title } => visitor.visit_footnote_block(title.as_deref()),
+    TreeElement::QuoteBlock(children) => visitor.visit_quote_block(children),
+    TreeElement::Iframe(raw) => visitor.visit_iframe(raw),
+    TreeElement::CodeBlock { language, source } => visitor.visit_code_block(language.as_deref(), source),
+    TreeElement::Tab { title, children } => visitor.visit_tab(title, children),
+    TreeElement::TabView(children) => visitor.visit_tab_view(children),
+    TreeElement::Table(rows) => visitor.visit_table(rows),
+    TreeElement::DefinitionList(items) => visitor.visit_definition_list(items),
+    TreeElement::Heading { level, children, anchor } => visitor.visit_heading(*level, children, anchor.as_deref()),
+    TreeElement::List { kind, children } => visitor.visit_list(*kind, children),
+    TreeElement::ListItem(children) => visitor.visit_list_item(children),
+    TreeElement::NewLine => visitor.visit_new_line(),
+    TreeElement::HtmlElement { tag, property, children } => visitor.visit_html_element(tag, property, children),
+    TreeElement::TableOfContents { float } => visitor.visit_table_of_contents(*float),
+    TreeElement::HorizontalRule => visitor.visit_horizontal_rule(),
+    TreeElement::ClearFloat(float) => visitor.visit_clear_float(*float),
+    TreeElement::Align { alignment, children } => visitor.visit_align(*alignment, children),
+    TreeElement::Image { source, alt, title, width, height, link, alignment, float, class, style } => visitor.visit_image(
+      source, alt.as_deref(), title.as_deref(), *width, *height, link.as_deref(), *alignment, *float, class.as_deref(), style.as_deref(),
+    ),
+  }
+  visitor.leave(element);
+}
+
+/// Rewrites the tree in place. `visit_elements` gets the whole `Vec`, so elements can also be removed, replaced or inserted.
+pub trait VisitorMut {
+  /// Called with every element before its variant's method. The element may be replaced here.
+  fn enter(&mut self, _element: &mut TreeElement) {}
+  /// Called with every element after its variant's method returned.
+  fn leave(&mut self, _element: &mut TreeElement) {}
+
+  fn visit_elements(&mut self, elements: &mut Vec<TreeElement>) {
+    for v in elements {
+      self.visit_element(v);
+    }
+  }
+
+  fn visit_element(&mut self, element: &mut TreeElement) {
+    walk_element_mut(self, element);
+  }
+
+  fn visit_paragraph(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_text(&mut self, _text: &mut String) {}
+
+  fn visit_bold(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_italics(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_underline(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_strikethrough(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_monospaced(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_superscript(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_subscript(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_colored(&mut self, _red: &mut u8, _green: &mut u8, _blue: &mut u8, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_size(&mut self, _scale: &mut CssSize, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_link(&mut self, _href: &mut Url, _open_in_new_tab: &mut bool, _name: &mut String) {}
+
+  fn visit_internal_link(&mut self, _href: &mut String, _open_in_new_tab: &mut bool, _name: &mut String) {}
+
+  fn visit_collapsible(&mut self, _show: &mut String, _hide: &mut String, _folded: &mut bool, _hide_location: &mut HideLocation, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_footnote(&mut self, _id: &mut u32, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_footnote_block(&mut self, _title: &mut Option<String>) {}
+
+  fn visit_quote_block(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_iframe(&mut self, _raw: &mut String) {}
+
+  fn visit_code_block(&mut self, _language: &mut Option<String>, _source: &mut String) {}
+
+  fn visit_tab(&mut self, _title: &mut String, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_tab_view(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  /// Visits the cells row by row.
+  fn visit_table(&mut self, rows: &mut Vec<Vec<Cell>>) {
+    for cell in rows.iter_mut().flatten() {
+      self.visit_cell(cell);
+    }
+  }
+
+  fn visit_cell(&mut self, cell: &mut Cell) {
+    self.visit_elements(&mut cell.val);
+  }
+
+  fn visit_definition_list(&mut self, items: &mut Vec<(Vec<TreeElement>, Vec<TreeElement>)>) {
+    for (term, definition) in items {
+      self.visit_elements(term);
+      self.visit_elements(definition);
+    }
+  }
+
+  fn visit_heading(&mut self, _level: &mut u8, children: &mut Vec<TreeElement>, _anchor: &mut Option<String>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_list(&mut self, _kind: &mut ListKind, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_list_item(&mut self, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_new_line(&mut self) {}
+
+  fn visit_html_element(&mut self, _tag: &mut String, _property: &mut Vec<(String, String)>, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  fn visit_table_of_contents(&mut self, _float: &mut Option<Float>) {}
+
+  fn visit_horizontal_rule(&mut self) {}
+
+  fn visit_clear_float(&mut self, _float: &mut Option<Float>) {}
+
+  fn visit_align(&mut self, _alignment: &mut TextAlign, children: &mut Vec<TreeElement>) {
+    self.visit_elements(children);
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn visit_image(
+    &mut self, _source: &mut String, _alt: &mut Option<String>, _title: &mut Option<String>, _width: &mut Option<u32>, _height: &mut Option<u32>,
+    _link: &mut Option<String>, _alignment: &mut Option<Alignment>, _float: &mut bool, _class: &mut Option<String>, _style: &mut Option<String>,
+  ) {}
+}
+
+/// Calls `enter`, the method for the variant of `element`, then `leave`. Does nothing when `element` is nested
+/// deeper than the renderers go.
+pub fn walk_element_mut<V: VisitorMut + ?Sized>(visitor: &mut V, element: &mut TreeElement) {
+  let Some(_guard) = DepthGuard::enter() else { return; };
+  visitor.enter(element);
+  match element {
+    TreeElement::Paragraph(children) => visitor.visit_paragraph(children),
+    TreeElement::Text(text) => visitor.visit_text(text),
+    TreeElement::Bold(children) => visitor.visit_bold(children),
+    TreeElement::Italics(children) => visitor.visit_italics(children),
+    TreeElement::Underline(children) => visitor.visit_underline(children),
+    TreeElement::Strikethrough(children) => visitor.visit_strikethrough(children),
+    TreeElement::Monospaced(children) => visitor.visit_monospaced(children),
+    TreeElement::Superscript(children) => visitor.visit_superscript(children),
+    TreeElement::Subscript(children) => visitor.visit_subscript(children),
+    TreeElement::Colored { red, green, blue, children } => visitor.visit_colored(red, green, blue, children),
+    TreeElement::Size { scale, children } => visitor.visit_size(scale, children),
+    TreeElement::Link { href, open_in_new_tab, name } => visitor.visit_link(href, open_in_new_tab, name),
+    TreeElement::InternalLink { href, open_in_new_tab, name } => visitor.visit_internal_link(href, open_in_new_tab, name),
+    TreeElement::Collapsible { show, hide, folded, hide_location, children } => visitor.visit_collapsible(show, hide, folded, hide_location, children),
+    TreeElement::Footnote { id, children } => visitor.visit_footnote(id, children),
+    TreeElement::FootnoteBlock { title } => visitor.visit_footnote_block(title),
+    TreeElement::QuoteBlock(children) => visitor.visit_quote_block(children),
+    TreeElement::Iframe(raw) => visitor.visit_iframe(raw),
+    TreeElement::CodeBlock { language, source } => visitor.visit_code_block(language, source),
+    TreeElement::Tab { title, children } => visitor.visit_tab(title, children),
+    TreeElement::TabView(children) => visitor.visit_tab_view(children),
+    TreeElement::Table(rows) => visitor.visit_table(rows),
+    TreeElement::DefinitionList(items) => visitor.visit_definition_list(items),
+    TreeElement::Heading { level, children, anchor } => visitor.visit_heading(level, children, anchor),
+    TreeElement::List { kind, children } => visitor.visit_list(kind, children),
+    TreeElement::ListItem(children) => visitor.visit_list_item(children),
+    TreeElement::NewLine => visitor.visit_new_line(),
+    TreeElement::HtmlElement { tag, property, children } => visitor.visit_html_element(tag, property, children),
+    TreeElement::TableOfContents { float } => visitor.visit_table_of_contents(float),
+    TreeElement::HorizontalRule => visitor.visit_horizontal_rule(),
+    TreeElement::ClearFloat(float) => visitor.visit_clear_float(float),
+    TreeElement::Align { alignment, children } => visitor.visit_align(alignment, children),
+    TreeElement::Image { source, alt, title, width, height, link, alignment, float, class, style } => {
+      visitor.visit_image(source, alt, title, width, height, link, alignment, float, class, style);
+    }
+  }
+  visitor.leave(element);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(s: &str) -> Vec<TreeElement> {
+    crate::parse_to_ast(String::from(s)).unwrap()
+  }
+
+  #[derive(Default)]
+  struct Links(Vec<String>);
+
+  impl Visitor for Links {
+    fn visit_link(&mut self, href: &Url, _open_in_new_tab: bool, _name: &str) {
+      self.0.push(href.0.clone());
+    }
+
+    fn visit_internal_link(&mut self, href: &str, _open_in_new_tab: bool, _name: &str) {
+      self.0.push(String::from(href));
+    }
+  }
+
+  #[test]
+  fn test_visitor() {
+    let ast = parse(concat!(
+      "[[[a]]]\n\n",
+      "||**[[[b]]]**||[https://example.com/c c]||\n\n",
+      "[[tabview]]\n[[tab T]]\n> [[[d]]][[footnote]][[[e]]][[/footnote]]\n[[/tab]]\n[[/tabview]]",
+    ));
+    let mut links = Links::default();
+    links.visit_elements(&ast);
+    assert_eq!(links.0, ["a", "b", "https://example.com/c", "d", "e"]);
+  }
+
+  #[derive(Default)]
+  struct Depth {
+    current: usize,
+    deepest: usize,
+  }
+
+  impl Visitor for Depth {
+    fn enter(&mut self, _element: &TreeElement) {
+      self.current += 1;
+      self.deepest = self.deepest.max(self.current);
+    }
+
+    fn leave(&mut self, _element: &TreeElement) {
+      self.current -= 1;
+    }
+  }
+
+  #[test]
+  fn test_enter_leave() {
+    let mut depth = Depth::default();
+    depth.visit_elements(&parse("a **b //c//**\n\nd"));
+    assert_eq!((depth.current, depth.deepest), (0, 4)); // Paragraph > Bold > Italics > Text
+  }
+
+  struct Rewrite;
+
+  impl VisitorMut for Rewrite {
+    fn visit_elements(&mut self, elements: &mut Vec<TreeElement>) {
+      elements.retain(|v| !matches!(v, TreeElement::Footnote { .. } | TreeElement::FootnoteBlock { .. }));
+      for v in elements {
+        self.visit_element(v);
+      }
+    }
+
+    fn visit_text(&mut self, text: &mut String) {
+      *text = text.to_uppercase();
+    }
+
+    fn visit_cell(&mut self, cell: &mut Cell) {
+      cell.style = None;
+      self.visit_elements(&mut cell.val);
+    }
+  }
+
+  #[test]
+  fn test_visitor_mut() {
+    let mut ast = parse("a **c**[[footnote]]b[[/footnote]]\n\n||~ d ||");
+    Rewrite.visit_elements(&mut ast);
+    assert_eq!(ast, parse("A **C**\n\n|| D ||"));
+  }
+
+  #[test]
+  fn test_too_deep() {
+    let nested = |depth: usize| (0..depth).fold(vec![TreeElement::Text(String::from("a"))], |v, _| vec![TreeElement::Bold(v)]);
+
+    let mut depth = Depth::default();
+    assert_eq!(visit(&mut depth, &nested(MAX_RENDER_DEPTH - 1)), Ok(()));
+    assert_eq!(depth.deepest, MAX_RENDER_DEPTH);
+
+    let mut depth = Depth::default();
+    assert_eq!(visit(&mut depth, &nested(MAX_RENDER_DEPTH)), Err(RenderError::NestingTooDeep { limit: MAX_RENDER_DEPTH }));
+    assert_eq!((depth.current, depth.deepest), (0, MAX_RENDER_DEPTH));
+
+    let mut ast = nested(MAX_RENDER_DEPTH);
+    assert_eq!(visit_mut(&mut Rewrite, &mut ast), Err(RenderError::NestingTooDeep { limit: MAX_RENDER_DEPTH }));
+    assert_eq!(visit(&mut Depth::default(), &parse("a")), Ok(()));
+  }
+}